@@ -0,0 +1,303 @@
+use crate::splitters::{CodeSplitter, Splitter, WordSplitter};
+use rust_stemmers::{Algorithm, Stemmer};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+// Common English words that carry no meaning for search. Single letters are left over from
+// contractions ("doesn't") and possessives ("parser's").
+pub const ENGLISH_STOPWORDS: [&str; 64] = [
+    "a", "about", "after", "all", "also", "an", "and", "any", "are", "as", "at", "be", "been",
+    "but", "by", "can", "could", "did", "do", "does", "for", "from", "had", "has", "have", "he",
+    "her", "his", "how", "i", "if", "in", "into", "is", "it", "its", "me", "my", "of", "on", "or",
+    "our", "s", "she", "so", "t", "that", "the", "their", "them", "then", "there", "these", "they",
+    "this", "to", "was", "we", "were", "what", "when", "which", "with", "you",
+];
+
+// A step of an `Analyzer` that transforms, drops or adds tokens.
+pub trait TokenFilter: Send + Sync {
+    fn filter(&self, tokens: Vec<String>) -> Vec<String>;
+
+    // identifies the filter as part of the analyzer's name
+    fn name(&self) -> String;
+}
+
+pub struct LowercaseFilter;
+
+// Decomposes characters with NFKD and drops the combining marks, so that "café" and "cafe" or
+// the ligature "ﬁx" and "fix" are the same token.
+pub struct UnicodeNormalizationFilter;
+
+pub struct StopwordFilter {
+    stopwords: HashSet<String>,
+}
+
+pub struct StemmerFilter {
+    stemmer: Stemmer,
+    language: String,
+}
+
+impl TokenFilter for LowercaseFilter {
+    fn filter(&self, tokens: Vec<String>) -> Vec<String> {
+        tokens
+            .into_iter()
+            .map(|token| token.to_lowercase())
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        String::from("lowercase")
+    }
+}
+
+impl TokenFilter for UnicodeNormalizationFilter {
+    fn filter(&self, tokens: Vec<String>) -> Vec<String> {
+        tokens
+            .into_iter()
+            .map(|token| token.nfkd().filter(|c| !is_combining_mark(*c)).collect())
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        String::from("nfkd")
+    }
+}
+
+impl StopwordFilter {
+    // The stopwords are compared to the tokens as is, so they should be normalized the same way
+    // as the tokens that reach the filter (e.g. lowercased).
+    pub fn new<I, S>(stopwords: I) -> StopwordFilter
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        StopwordFilter {
+            stopwords: stopwords.into_iter().map(|word| word.into()).collect(),
+        }
+    }
+
+    pub fn english() -> StopwordFilter {
+        StopwordFilter::new(ENGLISH_STOPWORDS)
+    }
+}
+
+impl TokenFilter for StopwordFilter {
+    fn filter(&self, tokens: Vec<String>) -> Vec<String> {
+        tokens
+            .into_iter()
+            .filter(|token| !self.stopwords.contains(token))
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        // the list changes which terms are indexed, so it has to be part of the name. the hash has
+        // to be stable across builds, which `DefaultHasher` does not promise, so this is FNV-1a
+        let mut stopwords: Vec<&String> = self.stopwords.iter().collect();
+        stopwords.sort();
+        let hash = stopwords.iter().fold(0xcbf29ce484222325u64, |hash, word| {
+            word.bytes()
+                .chain(std::iter::once(0))
+                .fold(hash, |hash, byte| {
+                    (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+                })
+        });
+        format!("stopwords-{:x}", hash)
+    }
+}
+
+impl StemmerFilter {
+    // Snowball stemming, the successor of the Porter stemmer for English.
+    pub fn english() -> StemmerFilter {
+        StemmerFilter {
+            stemmer: Stemmer::create(Algorithm::English),
+            language: String::from("english"),
+        }
+    }
+}
+
+impl TokenFilter for StemmerFilter {
+    fn filter(&self, tokens: Vec<String>) -> Vec<String> {
+        tokens
+            .into_iter()
+            .map(|token| self.stemmer.stem(&token).into_owned())
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        format!("stem-{}", self.language)
+    }
+}
+
+// A splitter followed by a chain of token filters. It is itself a `Splitter`, so that the same
+// analysis is applied to the documents when they are indexed and to the query when it is ranked.
+pub struct Analyzer {
+    splitter: Box<dyn Splitter>,
+    filters: Vec<Box<dyn TokenFilter>>,
+}
+
+pub struct AnalyzerBuilder {
+    splitter: Box<dyn Splitter>,
+    filters: Vec<Box<dyn TokenFilter>>,
+}
+
+impl AnalyzerBuilder {
+    pub fn new() -> AnalyzerBuilder {
+        AnalyzerBuilder {
+            splitter: Box::new(WordSplitter),
+            filters: Vec::new(),
+        }
+    }
+
+    pub fn splitter<S>(mut self, splitter: S) -> AnalyzerBuilder
+    where
+        S: Splitter + 'static,
+    {
+        self.splitter = Box::new(splitter);
+        self
+    }
+
+    // filters run in the order in which they are added
+    pub fn filter<F>(mut self, filter: F) -> AnalyzerBuilder
+    where
+        F: TokenFilter + 'static,
+    {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    pub fn build(self) -> Analyzer {
+        Analyzer {
+            splitter: self.splitter,
+            filters: self.filters,
+        }
+    }
+}
+
+impl Default for AnalyzerBuilder {
+    fn default() -> Self {
+        AnalyzerBuilder::new()
+    }
+}
+
+impl Analyzer {
+    pub fn builder() -> AnalyzerBuilder {
+        AnalyzerBuilder::new()
+    }
+}
+
+impl Splitter for Analyzer {
+    fn split(&self, s: &str) -> Vec<String> {
+        let tokens = self
+            .filters
+            .iter()
+            .fold(self.splitter.split(s), |tokens, filter| {
+                filter.filter(tokens)
+            });
+        tokens
+            .into_iter()
+            .filter(|token| !token.is_empty())
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        std::iter::once(self.splitter.name())
+            .chain(self.filters.iter().map(|filter| filter.name()))
+            .collect::<Vec<String>>()
+            .join("+")
+    }
+}
+
+// The analyzer of natural-language text: commit titles and bodies, issues, pull requests and
+// comments, and the queries that are ranked against them.
+pub fn text_analyzer() -> &'static Analyzer {
+    static TEXT_ANALYZER: OnceLock<Analyzer> = OnceLock::new();
+    TEXT_ANALYZER.get_or_init(|| {
+        Analyzer::builder()
+            .splitter(WordSplitter)
+            .filter(UnicodeNormalizationFilter)
+            .filter(LowercaseFilter)
+            .filter(StopwordFilter::english())
+            .filter(StemmerFilter::english())
+            .build()
+    })
+}
+
+// The analyzer of code and file paths: identifiers and paths are split into their words, which are
+// then analyzed like natural-language text so that `parseHeaders` matches "parsing the header".
+pub fn code_analyzer() -> &'static Analyzer {
+    static CODE_ANALYZER: OnceLock<Analyzer> = OnceLock::new();
+    CODE_ANALYZER.get_or_init(|| {
+        Analyzer::builder()
+            .splitter(CodeSplitter)
+            .filter(UnicodeNormalizationFilter)
+            .filter(LowercaseFilter)
+            .filter(StopwordFilter::english())
+            .filter(StemmerFilter::english())
+            .build()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equivalent_words() {
+        let analyzer = text_analyzer();
+        assert_eq!(analyzer.split("Fix"), analyzer.split("fix"));
+        assert_eq!(analyzer.split("fixes"), analyzer.split("fix"));
+        assert_eq!(analyzer.split("parser."), analyzer.split("parser"));
+        assert_eq!(analyzer.split("fix"), vec!["fix"]);
+    }
+
+    #[test]
+    fn test_diacritics_are_stripped() {
+        let analyzer = text_analyzer();
+        assert_eq!(analyzer.split("café"), analyzer.split("cafe"));
+        assert_eq!(
+            analyzer.split("naïve résumé"),
+            analyzer.split("naive resume")
+        );
+        assert_eq!(analyzer.split("ﬁx"), vec!["fix"]);
+    }
+
+    #[test]
+    fn test_stopwords_are_removed() {
+        let analyzer = text_analyzer();
+        assert_eq!(
+            analyzer.split("the parser of the file"),
+            analyzer.split("parser file")
+        );
+        assert!(analyzer.split("it is what it was").is_empty());
+        assert!(!code_analyzer()
+            .split("getTheParser")
+            .contains(&String::from("the")));
+    }
+
+    #[test]
+    fn test_index_and_query_terms_agree() {
+        // documents and queries go through the same analyzer, so a query term has to come out
+        // the same as the term indexed from the text it was written after
+        let analyzer = text_analyzer();
+        let indexed = analyzer.split("Fixes the crash when parsing empty headers.");
+        for query in ["fix crash", "parsing", "Empty header", "crashes"] {
+            for term in analyzer.split(query) {
+                assert!(indexed.contains(&term), "{} is not in {:?}", term, indexed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stopword_filter_name() {
+        // the name identifies the index, so it must not change between builds or list orders
+        let name = StopwordFilter::new(["the", "a"]).name();
+        assert_eq!(name, StopwordFilter::new(["a", "the"]).name());
+        assert_ne!(name, StopwordFilter::new(["the"]).name());
+        assert_ne!(
+            StopwordFilter::new(["ab", "c"]).name(),
+            StopwordFilter::new(["a", "bc"]).name()
+        );
+        assert_eq!(name, "stopwords-6bad226975a5fc75");
+    }
+}
@@ -0,0 +1,812 @@
+use crate::error::GitmError;
+use crate::git::Author;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+
+const GITHUB_NOREPLY_DOMAIN: &str = "users.noreply.github.com";
+// mentions shorter than this are never fuzzy matched, e.g. "the" is one edit away from "theo"
+const MIN_FUZZY_MENTION_LEN: usize = 5;
+const MIN_PREFIX_MENTION_LEN: usize = 4;
+
+// Groups of interchangeable first names, the first of which is the formal one.
+const NICKNAMES: [&[&str]; 52] = [
+    &["abigail", "abby"],
+    &["alexander", "alex", "sasha"],
+    &["alexandra", "alex", "lexi"],
+    &["andrew", "andy", "drew"],
+    &["anthony", "tony"],
+    &["benjamin", "ben", "benny"],
+    &["catherine", "cathy", "cat", "kate"],
+    &["charles", "charlie", "chuck"],
+    &["christopher", "chris"],
+    &["christine", "chris", "tina"],
+    &["daniel", "dan", "danny"],
+    &["david", "dave"],
+    &["deborah", "debbie", "deb"],
+    &["edward", "ed", "eddie", "ted"],
+    &["elizabeth", "liz", "beth", "betty", "lizzie"],
+    &["frederick", "fred", "freddie"],
+    &["gabriel", "gabe"],
+    &["gregory", "greg"],
+    &["james", "jim", "jimmy", "jamie"],
+    &["jennifer", "jen", "jenny"],
+    &["jessica", "jess"],
+    &["jeffrey", "jeff"],
+    &["john", "johnny", "jack"],
+    &["jonathan", "jon"],
+    &["joseph", "joe", "joey"],
+    &["joshua", "josh"],
+    &["katherine", "kate", "katie", "kathy"],
+    &["kenneth", "ken", "kenny"],
+    &["kimberly", "kim"],
+    &["lawrence", "larry"],
+    &["margaret", "meg", "maggie", "peggy"],
+    &["matthew", "matt"],
+    &["michael", "mike", "mikey", "mick"],
+    &["nathan", "nate"],
+    &["nathaniel", "nate", "nat"],
+    &["nicholas", "nick", "nicky"],
+    &["patricia", "pat", "patty", "trish"],
+    &["patrick", "pat", "paddy"],
+    &["peter", "pete"],
+    &["philip", "phil"],
+    &["raymond", "ray"],
+    &["rebecca", "becky", "becca"],
+    &["richard", "rich", "rick", "dick"],
+    &["robert", "rob", "bob", "bobby", "robbie"],
+    &["ronald", "ron", "ronnie"],
+    &["samuel", "sam", "sammy"],
+    &["samantha", "sam"],
+    &["stephen", "steve"],
+    &["steven", "steve"],
+    &["thomas", "tom", "tommy"],
+    &["timothy", "tim"],
+    &["william", "will", "bill", "billy", "liam"],
+];
+
+// (name, email), either of which can be missing
+type NameAndEmail = (Option<String>, Option<String>);
+// a way of interpreting a mention, see `AuthorResolver::resolve`
+type MatchTier = dyn Fn(&Identity, &str) -> bool;
+
+// A .mailmap, which maps the names and emails that commits were made with to canonical ones.
+// See gitmailmap(5).
+#[derive(Debug, Clone, Default)]
+pub struct Mailmap {
+    // keyed by the lowercased commit email and, for entries that also give the commit name, the
+    // lowercased commit name
+    entries: HashMap<(String, Option<String>), NameAndEmail>,
+}
+
+impl Mailmap {
+    // A missing mailmap is the same as an empty one.
+    pub fn open(path: &Path) -> Result<Mailmap, GitmError> {
+        if !path.exists() {
+            return Ok(Mailmap::default());
+        }
+        Ok(Mailmap::parse(fs::read_to_string(path)?.as_str()))
+    }
+
+    pub fn parse(s: &str) -> Mailmap {
+        let mut entries = HashMap::new();
+        for line in s.lines() {
+            let line = match line.split_once('#') {
+                Some((line, _)) => line,
+                None => line,
+            };
+            // every entry is a list of "Name <email>" pairs in which the names are optional:
+            // the first pair is the canonical identity and the second, if any, the commit identity
+            let mut pairs: Vec<(Option<String>, String)> = Vec::new();
+            let mut rest = line;
+            while let Some(start) = rest.find('<') {
+                let end = match rest[start..].find('>') {
+                    Some(end) => start + end,
+                    None => break,
+                };
+                let name = rest[..start].trim();
+                pairs.push((
+                    (!name.is_empty()).then(|| name.to_string()),
+                    rest[start + 1..end].trim().to_string(),
+                ));
+                rest = &rest[end + 1..];
+            }
+            let (proper, commit) = match pairs.len() {
+                // "Proper Name <commit@email>"
+                1 => ((pairs[0].0.clone(), None), (None, pairs[0].1.clone())),
+                // "[Proper Name] <proper@email> [Commit Name] <commit@email>"
+                2 => (
+                    (pairs[0].0.clone(), Some(pairs[0].1.clone())),
+                    (pairs[1].0.clone(), pairs[1].1.clone()),
+                ),
+                _ => continue,
+            };
+            let (commit_name, commit_email) = commit;
+            entries.insert(
+                (
+                    commit_email.to_lowercase(),
+                    commit_name.map(|name| name.to_lowercase()),
+                ),
+                proper,
+            );
+        }
+        Mailmap { entries }
+    }
+
+    // Returns the canonical name and email of an author, like %aN and %aE in git log.
+    pub fn map(&self, name: Option<&str>, email: Option<&str>) -> NameAndEmail {
+        let mut canonical = (name.map(String::from), email.map(String::from));
+        if let Some((proper_name, proper_email)) = self.entry(name, email) {
+            if proper_name.is_some() {
+                canonical.0 = proper_name.clone();
+            }
+            if proper_email.is_some() {
+                canonical.1 = proper_email.clone();
+            }
+        }
+        canonical
+    }
+
+    // The proper name that an entry explicitly gives the author, if any.
+    fn proper_name(&self, name: Option<&str>, email: Option<&str>) -> Option<&str> {
+        self.entry(name, email)?.0.as_deref()
+    }
+
+    fn entry(&self, name: Option<&str>, email: Option<&str>) -> Option<&NameAndEmail> {
+        let email = email?.to_lowercase();
+        // an entry for the exact name and email takes precedence over one for the email only
+        self.entries
+            .get(&(email.clone(), name.map(|name| name.to_lowercase())))
+            .or_else(|| self.entries.get(&(email, None)))
+    }
+}
+
+// A person behind possibly several git names, emails and GitHub logins. All but `name` are
+// lowercased.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    // the name the person commits with most often, or their login if they never committed
+    pub name: String,
+    pub names: HashSet<String>,
+    pub emails: HashSet<String>,
+    pub logins: HashSet<String>,
+}
+
+impl Identity {
+    // Whether a commit, issue or comment author is this person.
+    pub fn matches(&self, author: &Author) -> bool {
+        let is_in = |value: &Option<String>, values: &HashSet<String>| {
+            value
+                .as_ref()
+                .is_some_and(|value| values.contains(&value.to_lowercase()))
+        };
+        is_in(&author.email, &self.emails)
+            || is_in(&author.username, &self.logins)
+            || (author.username.is_none() && is_in(&author.name, &self.names))
+    }
+
+    fn first_names(&self) -> HashSet<&str> {
+        self.names
+            .iter()
+            .filter_map(|name| name.split_whitespace().next())
+            .collect()
+    }
+
+    fn email_local_parts(&self) -> HashSet<&str> {
+        self.emails
+            .iter()
+            .filter_map(|email| email.split_once('@').map(|(local_part, _)| local_part))
+            .collect()
+    }
+}
+
+impl Display for Identity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut details: Vec<String> = self
+            .logins
+            .iter()
+            .map(|login| format!("@{}", login))
+            .collect();
+        details.sort();
+        let mut emails: Vec<&String> = self.emails.iter().collect();
+        emails.sort();
+        details.extend(emails.into_iter().map(|email| format!("<{}>", email)));
+        if details.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} {}", self.name, details.join(" "))
+        }
+    }
+}
+
+// Resolves author mentions like "johns", "@jsmith" or "bob" to the people of a repository. Git
+// identities are canonicalized with the .mailmap and merged when they share an email or the
+// .mailmap gives them the same name; a shared name alone (e.g. "root" or "dependabot") does not
+// merge identities, as unrelated people and bots commit under the same generic names. GitHub
+// logins are attached to the git identity that they most likely belong to.
+#[derive(Debug, Clone, Default)]
+pub struct AuthorResolver {
+    identities: Vec<Identity>,
+}
+
+impl AuthorResolver {
+    // `authors` has one entry per commit, so that the most used name of a person can be picked.
+    pub fn new(authors: Vec<Author>, mailmap: &Mailmap) -> AuthorResolver {
+        // union-find over the distinct (name, email) pairs
+        let mut pairs: Vec<NameAndEmail> = Vec::new();
+        let mut pair_ids: HashMap<NameAndEmail, usize> = HashMap::new();
+        let mut counts: Vec<usize> = Vec::new();
+        let mut parents: Vec<usize> = Vec::new();
+        let mut owners: HashMap<String, usize> = HashMap::new();
+        for author in authors {
+            let raw = (
+                author.name.filter(|name| !name.is_empty()),
+                author.email.filter(|email| !email.is_empty()),
+            );
+            let id = *pair_ids.entry(raw.clone()).or_insert_with(|| {
+                pairs.push(raw.clone());
+                counts.push(0);
+                parents.push(pairs.len() - 1);
+                pairs.len() - 1
+            });
+            counts[id] += 1;
+            let (_, email) = mailmap.map(raw.0.as_deref(), raw.1.as_deref());
+            let keys = [
+                mailmap
+                    .proper_name(raw.0.as_deref(), raw.1.as_deref())
+                    .map(|name| format!("name:{}", name.to_lowercase())),
+                email.map(|email| format!("email:{}", email.to_lowercase())),
+            ];
+            for key in keys.into_iter().flatten() {
+                match owners.get(&key) {
+                    Some(&owner) => union(&mut parents, owner, id),
+                    None => {
+                        owners.insert(key, id);
+                    }
+                }
+            }
+        }
+        let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+        for id in 0..pairs.len() {
+            groups.entry(find(&mut parents, id)).or_default().push(id);
+        }
+        let mut identities = Vec::with_capacity(groups.len());
+        for (_, ids) in groups {
+            let mut name_counts: HashMap<String, usize> = HashMap::new();
+            let mut identity = Identity {
+                name: String::new(),
+                names: HashSet::new(),
+                emails: HashSet::new(),
+                logins: HashSet::new(),
+            };
+            for id in ids {
+                let (raw_name, raw_email) = &pairs[id];
+                let (name, email) = mailmap.map(raw_name.as_deref(), raw_email.as_deref());
+                for name in [raw_name.clone(), name.clone()].into_iter().flatten() {
+                    identity.names.insert(name.to_lowercase());
+                }
+                for email in [raw_email.clone(), email].into_iter().flatten() {
+                    identity.emails.insert(email.to_lowercase());
+                    if let Some(login) = noreply_login(email.as_str()) {
+                        identity.logins.insert(login);
+                    }
+                }
+                if let Some(name) = name {
+                    *name_counts.entry(name).or_insert(0) += counts[id];
+                }
+            }
+            identity.name = name_counts
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+                .map(|(name, _)| name)
+                .or_else(|| identity.emails.iter().min().cloned())
+                .unwrap_or_default();
+            identities.push(identity);
+        }
+        identities.sort_by(|a, b| a.name.cmp(&b.name));
+        AuthorResolver { identities }
+    }
+
+    pub fn identities(&self) -> &[Identity] {
+        &self.identities
+    }
+
+    // Attaches GitHub logins to the git identities that they belong to. A login is attached if
+    // it appears in a noreply email, equals an email local part or equals a name without spaces,
+    // and only one identity qualifies. Other logins become identities of their own.
+    pub fn add_logins<I>(&mut self, logins: I)
+    where
+        I: IntoIterator<Item = String>,
+    {
+        for login in logins {
+            let login = login.to_lowercase();
+            if login.is_empty() || self.identities.iter().any(|i| i.logins.contains(&login)) {
+                continue;
+            }
+            let candidates: Vec<usize> = (0..self.identities.len())
+                .filter(|&i| {
+                    let identity = &self.identities[i];
+                    identity.email_local_parts().contains(login.as_str())
+                        || identity
+                            .names
+                            .iter()
+                            .any(|name| name.split_whitespace().collect::<String>() == login)
+                })
+                .collect();
+            match candidates.as_slice() {
+                [i] => {
+                    self.identities[*i].logins.insert(login);
+                }
+                _ => self.identities.push(Identity {
+                    name: login.clone(),
+                    names: HashSet::new(),
+                    emails: HashSet::new(),
+                    logins: HashSet::from([login]),
+                }),
+            }
+        }
+    }
+
+    // Resolves a mention to a single identity. Mentions are tried from the most to the least
+    // specific interpretation (full name, email or login, then first name, nickname and typos),
+    // and an ambiguous interpretation resolves to nothing rather than to a guess.
+    pub fn resolve(&self, mention: &str) -> Option<&Identity> {
+        let mention = mention.trim().trim_start_matches('@').to_lowercase();
+        if mention.is_empty() {
+            return None;
+        }
+        let mut forms = vec![mention.clone()];
+        // "john's" or "johns" as in "johns commits"
+        for suffix in ["'s", "s"] {
+            if let Some(form) = mention.strip_suffix(suffix) {
+                if !form.is_empty() {
+                    forms.push(form.to_string());
+                }
+            }
+        }
+        let tiers: [&MatchTier; 5] = [
+            &|identity, form| {
+                identity.names.contains(form)
+                    || identity.emails.contains(form)
+                    || identity.logins.contains(form)
+                    || identity.email_local_parts().contains(form)
+            },
+            &|identity, form| identity.first_names().contains(form),
+            &|identity, form| {
+                let first_names = identity.first_names();
+                nicknames_of(form)
+                    .into_iter()
+                    .any(|nickname| first_names.contains(nickname))
+            },
+            &|identity, form| {
+                form.len() >= MIN_PREFIX_MENTION_LEN
+                    && identity
+                        .first_names()
+                        .into_iter()
+                        .any(|first_name| first_name.starts_with(form))
+            },
+            &|identity, form| {
+                form.chars().count() >= MIN_FUZZY_MENTION_LEN
+                    && identity
+                        .first_names()
+                        .into_iter()
+                        .chain(identity.logins.iter().map(String::as_str))
+                        .chain(identity.email_local_parts())
+                        .any(|candidate| edit_distance(candidate, form) <= 1)
+            },
+        ];
+        for tier in tiers {
+            for form in forms.iter() {
+                let matches: Vec<&Identity> = self
+                    .identities
+                    .iter()
+                    .filter(|identity| tier(identity, form.as_str()))
+                    .collect();
+                match matches.as_slice() {
+                    [] => continue,
+                    [identity] => return Some(identity),
+                    _ => return None,
+                }
+            }
+        }
+        None
+    }
+}
+
+// e.g. jsmith for jsmith@users.noreply.github.com and 1234+jsmith@users.noreply.github.com
+fn noreply_login(email: &str) -> Option<String> {
+    let (local_part, domain) = email.split_once('@')?;
+    if !domain.eq_ignore_ascii_case(GITHUB_NOREPLY_DOMAIN) {
+        return None;
+    }
+    let login = match local_part.split_once('+') {
+        Some((_, login)) => login,
+        None => local_part,
+    };
+    Some(login.to_lowercase())
+}
+
+fn nicknames_of(name: &str) -> HashSet<&'static str> {
+    NICKNAMES
+        .iter()
+        .filter(|group| group.contains(&name))
+        .flat_map(|group| group.iter().copied())
+        .filter(|nickname| *nickname != name)
+        .collect()
+}
+
+fn find(parents: &mut [usize], mut id: usize) -> usize {
+    while parents[id] != id {
+        parents[id] = parents[parents[id]];
+        id = parents[id];
+    }
+    id
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    if a != b {
+        parents[b] = a;
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            current[j + 1] = (previous[j] + (a_char != *b_char) as usize)
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn author(name: &str, email: &str) -> Author {
+        Author {
+            name: Some(name.to_string()),
+            username: None,
+            email: Some(email.to_string()),
+        }
+    }
+
+    fn names(identity: &Identity) -> Vec<&str> {
+        let mut names: Vec<&str> = identity.names.iter().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    fn resolved_name(resolver: &AuthorResolver, mention: &str) -> Option<String> {
+        resolver
+            .resolve(mention)
+            .map(|identity| identity.name.clone())
+    }
+
+    #[test]
+    fn test_mailmap_parse() {
+        let mailmap = Mailmap::parse(
+            "# comment\n\
+             Jane Doe <jane@example.com>\n\
+             <jane@example.com> <jane@old.example.com>\n\
+             Jane Doe <jane@example.com> jdoe <JDoe@Laptop.local> # trailing comment\n\
+             John Smith <john@example.com> <john@old.example.com>\n\
+             not an entry\n\
+             Broken <unterminated\n",
+        );
+        // the proper name only
+        assert_eq!(
+            mailmap.map(Some("jane"), Some("jane@example.com")),
+            (
+                Some(String::from("Jane Doe")),
+                Some(String::from("jane@example.com"))
+            )
+        );
+        // the proper email only
+        assert_eq!(
+            mailmap.map(Some("Jane"), Some("jane@old.example.com")),
+            (
+                Some(String::from("Jane")),
+                Some(String::from("jane@example.com"))
+            )
+        );
+        // emails and names are matched case-insensitively
+        assert_eq!(
+            mailmap.map(Some("JDOE"), Some("jdoe@laptop.local")),
+            (
+                Some(String::from("Jane Doe")),
+                Some(String::from("jane@example.com"))
+            )
+        );
+        // the entry for jdoe@laptop.local requires the commit name
+        assert_eq!(
+            mailmap.map(Some("someone"), Some("jdoe@laptop.local")),
+            (
+                Some(String::from("someone")),
+                Some(String::from("jdoe@laptop.local"))
+            )
+        );
+        assert_eq!(
+            mailmap.map(Some("john"), Some("john@old.example.com")),
+            (
+                Some(String::from("John Smith")),
+                Some(String::from("john@example.com"))
+            )
+        );
+        assert_eq!(
+            mailmap.map(Some("Broken"), None),
+            (Some(String::from("Broken")), None)
+        );
+        assert_eq!(mailmap.entries.len(), 4);
+    }
+
+    #[test]
+    fn test_mailmap_name_and_email_entry_takes_precedence() {
+        let mailmap = Mailmap::parse(
+            "Shared Account <ops@example.com>\n\
+             Alice <alice@example.com> alice <ops@example.com>\n",
+        );
+        assert_eq!(
+            mailmap.map(Some("Alice"), Some("OPS@example.com")).1,
+            Some(String::from("alice@example.com"))
+        );
+        assert_eq!(
+            mailmap.map(Some("bob"), Some("ops@example.com")).0,
+            Some(String::from("Shared Account"))
+        );
+    }
+
+    #[test]
+    fn test_missing_mailmap_is_empty() {
+        let mailmap = Mailmap::open(Path::new("/nonexistent/.mailmap")).unwrap();
+        assert!(mailmap.entries.is_empty());
+    }
+
+    #[test]
+    fn test_merges_identities_by_email() {
+        let resolver = AuthorResolver::new(
+            vec![
+                author("Jane Doe", "jane@example.com"),
+                author("Jane Doe", "jane@example.com"),
+                author("jdoe", "JANE@example.com"),
+            ],
+            &Mailmap::default(),
+        );
+        assert_eq!(resolver.identities().len(), 1);
+        let identity = &resolver.identities()[0];
+        // the most used name
+        assert_eq!(identity.name, "Jane Doe");
+        assert_eq!(names(identity), vec!["jane doe", "jdoe"]);
+    }
+
+    #[test]
+    fn test_does_not_merge_identities_by_name() {
+        let resolver = AuthorResolver::new(
+            vec![
+                author("root", "root@build-server.local"),
+                author("root", "root@laptop.local"),
+                author(
+                    "dependabot[bot]",
+                    "49699333+dependabot[bot]@users.noreply.github.com",
+                ),
+                author("dependabot[bot]", "support@dependabot.com"),
+            ],
+            &Mailmap::default(),
+        );
+        assert_eq!(resolver.identities().len(), 4);
+        // a name that several identities share is ambiguous
+        assert_eq!(resolver.resolve("root"), None);
+    }
+
+    #[test]
+    fn test_merges_identities_by_mailmap() {
+        let mailmap = Mailmap::parse(
+            "Jane Doe <jane@example.com>\n\
+             Jane Doe <jane@personal.example.org>\n\
+             <john@example.com> <john@old.example.com>\n",
+        );
+        let resolver = AuthorResolver::new(
+            vec![
+                author("jane", "jane@example.com"),
+                author("J. Doe", "jane@personal.example.org"),
+                author("John Smith", "john@example.com"),
+                author("johnny", "john@old.example.com"),
+            ],
+            &mailmap,
+        );
+        assert_eq!(resolver.identities().len(), 2);
+        let jane = resolver.resolve("jane@personal.example.org").unwrap();
+        assert_eq!(jane.name, "Jane Doe");
+        assert!(jane.emails.contains("jane@example.com"));
+        let john = resolver.resolve("john@old.example.com").unwrap();
+        assert_eq!(names(john), vec!["john smith", "johnny"]);
+    }
+
+    #[test]
+    fn test_noreply_email_adds_login() {
+        let resolver = AuthorResolver::new(
+            vec![author("Jane Doe", "1234+JaneDoe@users.noreply.github.com")],
+            &Mailmap::default(),
+        );
+        let identity = resolver.resolve("@janedoe").unwrap();
+        assert!(identity.logins.contains("janedoe"));
+    }
+
+    #[test]
+    fn test_add_logins() {
+        let mut resolver = AuthorResolver::new(
+            vec![
+                author("Jane Doe", "jdoe@example.com"),
+                author("Bob Stone", "bob@example.com"),
+                author("Bob Miller", "bob@example.org"),
+            ],
+            &Mailmap::default(),
+        );
+        resolver.add_logins(vec![
+            String::from("JDoe"),
+            String::from("janedoe"),
+            String::from("bob"),
+            String::from("octocat"),
+        ]);
+        assert_eq!(
+            resolved_name(&resolver, "@jdoe"),
+            Some(String::from("Jane Doe"))
+        );
+        assert_eq!(
+            resolved_name(&resolver, "@janedoe"),
+            Some(String::from("Jane Doe"))
+        );
+        // both bobs have the local part bob, so the login becomes its own identity, and a mention of
+        // it is ambiguous
+        assert!(resolver
+            .identities()
+            .iter()
+            .any(|identity| identity.name == "bob" && identity.emails.is_empty()));
+        assert_eq!(resolved_name(&resolver, "@bob"), None);
+        assert_eq!(
+            resolved_name(&resolver, "@octocat"),
+            Some(String::from("octocat"))
+        );
+        assert_eq!(resolver.identities().len(), 5);
+    }
+
+    fn resolver() -> AuthorResolver {
+        AuthorResolver::new(
+            vec![
+                author("Jane Doe", "jane@example.com"),
+                author("Robert Smith", "rsmith@example.com"),
+                author("Christopher Lee", "clee@example.com"),
+                author("Christine Park", "cpark@example.com"),
+                author("Theodore Nguyen", "tnguyen@example.com"),
+                author("Margaret Hall", "mhall@example.com"),
+            ],
+            &Mailmap::default(),
+        )
+    }
+
+    #[test]
+    fn test_resolve_exact() {
+        let resolver = resolver();
+        assert_eq!(
+            resolved_name(&resolver, "Jane Doe"),
+            Some(String::from("Jane Doe"))
+        );
+        assert_eq!(
+            resolved_name(&resolver, "RSMITH@example.com"),
+            Some(String::from("Robert Smith"))
+        );
+        assert_eq!(
+            resolved_name(&resolver, "rsmith"),
+            Some(String::from("Robert Smith"))
+        );
+        // possessive forms
+        assert_eq!(
+            resolved_name(&resolver, "rsmith's"),
+            Some(String::from("Robert Smith"))
+        );
+        assert_eq!(
+            resolved_name(&resolver, "janes"),
+            Some(String::from("Jane Doe"))
+        );
+        assert_eq!(resolved_name(&resolver, ""), None);
+        assert_eq!(resolved_name(&resolver, "nobody"), None);
+    }
+
+    #[test]
+    fn test_resolve_first_name() {
+        let resolver = resolver();
+        assert_eq!(
+            resolved_name(&resolver, "jane"),
+            Some(String::from("Jane Doe"))
+        );
+        assert_eq!(
+            resolved_name(&resolver, "Robert"),
+            Some(String::from("Robert Smith"))
+        );
+    }
+
+    #[test]
+    fn test_resolve_nickname() {
+        let resolver = resolver();
+        assert_eq!(
+            resolved_name(&resolver, "bob"),
+            Some(String::from("Robert Smith"))
+        );
+        assert_eq!(
+            resolved_name(&resolver, "maggie"),
+            Some(String::from("Margaret Hall"))
+        );
+        // chris is short for both christopher and christine
+        assert_eq!(resolved_name(&resolver, "chris"), None);
+    }
+
+    #[test]
+    fn test_resolve_prefix() {
+        let resolver = resolver();
+        assert_eq!(
+            resolved_name(&resolver, "theo"),
+            Some(String::from("Theodore Nguyen"))
+        );
+        assert_eq!(
+            resolved_name(&resolver, "marg"),
+            Some(String::from("Margaret Hall"))
+        );
+        // too short to be a prefix
+        assert_eq!(resolved_name(&resolver, "the"), None);
+        // a prefix of both christopher and christine
+        assert_eq!(resolved_name(&resolver, "christ"), None);
+    }
+
+    #[test]
+    fn test_resolve_typo() {
+        let resolver = resolver();
+        assert_eq!(resolved_name(&resolver, "robret"), None);
+        assert_eq!(
+            resolved_name(&resolver, "robertt"),
+            Some(String::from("Robert Smith"))
+        );
+        assert_eq!(
+            resolved_name(&resolver, "tnguyn"),
+            Some(String::from("Theodore Nguyen"))
+        );
+        // too short to be fuzzy matched
+        assert_eq!(resolved_name(&resolver, "jame"), None);
+    }
+
+    #[test]
+    fn test_resolve_prefers_more_specific_tier() {
+        let resolver = AuthorResolver::new(
+            vec![
+                author("Will Turner", "will@example.com"),
+                author("William Jones", "wjones@example.com"),
+            ],
+            &Mailmap::default(),
+        );
+        // a first name beats the nickname of another first name
+        assert_eq!(
+            resolved_name(&resolver, "will"),
+            Some(String::from("Will Turner"))
+        );
+        assert_eq!(
+            resolved_name(&resolver, "william"),
+            Some(String::from("William Jones"))
+        );
+        // bill is a nickname of both first names
+        assert_eq!(resolved_name(&resolver, "bill"), None);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("robert", "robert"), 0);
+        assert_eq!(edit_distance("robert", "robret"), 2);
+        assert_eq!(edit_distance("robert", "roberta"), 1);
+        assert_eq!(edit_distance("", "bob"), 3);
+    }
+}
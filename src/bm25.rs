@@ -1,214 +1,434 @@
+use crate::document::{DocumentStats, Field, Fielded, FIELDS};
+use crate::error::GitmError;
 use crate::rankers::{Ranker, RankingResult};
-use crate::retrievers::Retriever;
-use crate::splitters::{Splitter, WhitespaceSplitter};
-use crate::store::Store;
-use std::collections::BinaryHeap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
 use std::fmt::Display;
 
 const DEFAULT_K1: f64 = 1.2;
 const DEFAULT_B: f64 = 0.75;
+const DEFAULT_SHORT_FIELD_B: f64 = 0.5;
+pub const DEFAULT_TITLE_BOOST: f64 = 3.0;
+pub const DEFAULT_BODY_BOOST: f64 = 1.0;
+pub const DEFAULT_CODE_BOOST: f64 = 0.5;
+pub const DEFAULT_PATHS_BOOST: f64 = 1.5;
 
-pub struct BM25Ranker<'a> {
+#[derive(Debug, PartialEq)]
+struct ScoredDoc {
+    score: f64,
+    doc_id: usize,
+}
+
+impl Eq for ScoredDoc {}
+
+impl PartialOrd for ScoredDoc {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScoredDoc {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.doc_id.cmp(&self.doc_id))
+    }
+}
+
+// The `k` best scored documents of the corpus, best first.
+fn top_k<U>(scores: HashMap<usize, f64>, corpus: &[U], k: usize) -> Vec<RankingResult<U>>
+where
+    U: Display + Clone,
+{
+    let mut heap = BinaryHeap::<Reverse<ScoredDoc>>::with_capacity(k + 1);
+    for (doc_id, score) in scores {
+        if doc_id >= corpus.len() {
+            continue;
+        }
+        heap.push(Reverse(ScoredDoc { score, doc_id }));
+        if heap.len() > k {
+            heap.pop();
+        }
+    }
+    let mut ranked_results: Vec<RankingResult<U>> = heap
+        .into_iter()
+        .map(|Reverse(scored_doc)| RankingResult::<U> {
+            score: scored_doc.score,
+            item: corpus[scored_doc.doc_id].clone(),
+        })
+        .collect();
+    ranked_results.sort_by(|a, b| b.cmp(a));
+    ranked_results
+}
+
+// How much a match in each field counts: a title match more than a body match, which counts more
+// than a match in the code of a patch.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldBoosts {
+    pub title: f64,
+    pub body: f64,
+    pub code: f64,
+    pub paths: f64,
+}
+
+impl Default for FieldBoosts {
+    fn default() -> Self {
+        FieldBoosts {
+            title: DEFAULT_TITLE_BOOST,
+            body: DEFAULT_BODY_BOOST,
+            code: DEFAULT_CODE_BOOST,
+            paths: DEFAULT_PATHS_BOOST,
+        }
+    }
+}
+
+impl FieldBoosts {
+    pub fn get(&self, field: Field) -> f64 {
+        match field {
+            Field::Title => self.title,
+            Field::Body => self.body,
+            Field::Code => self.code,
+            Field::Paths => self.paths,
+        }
+    }
+}
+
+// BM25F: the term frequencies of the fields are length normalized per field, weighted by the
+// field's boost and summed before saturation, so that a term that is repeated across fields does
+// not count as several independent matches.
+pub struct BM25FRanker {
     k1: f64,
-    b: f64,
-    splitter: &'a dyn Splitter,
+    boosts: FieldBoosts,
+    // length normalization per field, indexed by `Field::index`
+    b: [f64; FIELDS.len()],
 }
 
-pub struct BM25RankerBuilder {
+pub struct BM25FRankerBuilder {
     k1: f64,
-    b: f64,
-    splitter: &'static dyn Splitter,
+    boosts: FieldBoosts,
+    b: [f64; FIELDS.len()],
 }
 
-impl BM25RankerBuilder {
-    pub fn new() -> BM25RankerBuilder {
-        BM25RankerBuilder {
+impl BM25FRankerBuilder {
+    pub fn new() -> BM25FRankerBuilder {
+        BM25FRankerBuilder {
             k1: DEFAULT_K1,
-            b: DEFAULT_B,
-            splitter: &WhitespaceSplitter,
+            boosts: FieldBoosts::default(),
+            b: FIELDS.map(|field| match field {
+                // titles and paths are short, and a long one is not less relevant
+                Field::Title | Field::Paths => DEFAULT_SHORT_FIELD_B,
+                Field::Body | Field::Code => DEFAULT_B,
+            }),
         }
     }
 
-    pub fn k1(mut self, k1: f64) -> BM25RankerBuilder {
+    pub fn k1(mut self, k1: f64) -> BM25FRankerBuilder {
         self.k1 = k1;
         self
     }
 
-    pub fn b(mut self, b: f64) -> BM25RankerBuilder {
-        self.b = b;
+    pub fn boosts(mut self, boosts: FieldBoosts) -> BM25FRankerBuilder {
+        self.boosts = boosts;
         self
     }
 
-    pub fn splitter(mut self, splitter: &'static dyn Splitter) -> BM25RankerBuilder {
-        self.splitter = splitter;
+    pub fn b(mut self, field: Field, b: f64) -> BM25FRankerBuilder {
+        self.b[field.index()] = b;
         self
     }
 
-    // include lifetime parameter to ensure that the Splitter is not dropped
-    pub fn build(self) -> BM25Ranker<'static> {
-        BM25Ranker {
+    pub fn build(self) -> BM25FRanker {
+        BM25FRanker {
             k1: self.k1,
+            boosts: self.boosts,
             b: self.b,
-            splitter: self.splitter,
         }
     }
 }
 
-impl BM25Ranker<'_> {
-    pub fn new() -> BM25Ranker<'static> {
-        BM25Ranker::builder().build()
+impl Default for BM25FRankerBuilder {
+    fn default() -> Self {
+        BM25FRankerBuilder::new()
     }
+}
 
-    pub fn builder() -> BM25RankerBuilder {
-        BM25RankerBuilder::new()
-    }
+pub struct FieldedIndex {
+    postings: HashMap<String, Vec<FieldedPosting>>,
+    field_lens: Vec<[usize; FIELDS.len()]>,
+    avg_field_lens: [f64; FIELDS.len()],
 }
 
-fn doc_score<T>(
-    doc: T,
-    query_terms: &Vec<String>,
-    corpus: &Vec<T>,
-    splitter: &dyn Splitter,
-    avg_doc_len: f64,
-    k1: f64,
-    b: f64,
-) -> f64
-where
-    T: Display + Clone,
-{
-    let doc_terms = splitter.split(doc.to_string().as_str());
-    let mut score = 0.0;
-    for q_i in query_terms.iter() {
-        let idf = idf(q_i.as_str(), corpus);
-        let mut q_i_doc = 0;
-        for term in doc_terms.iter() {
-            if term == q_i {
-                q_i_doc += 1;
+#[derive(Debug, Clone, Copy)]
+struct FieldedPosting {
+    doc_id: usize,
+    term_freqs: [usize; FIELDS.len()],
+}
+
+impl FieldedIndex {
+    pub fn build<T>(corpus: &[T]) -> FieldedIndex
+    where
+        T: Fielded,
+    {
+        let document_stats: Vec<DocumentStats> =
+            corpus.iter().map(|doc| doc.document().stats()).collect();
+        FieldedIndex::from_document_stats(document_stats.iter())
+    }
+
+    // Builds the index from precomputed statistics (e.g. the ones persisted in the commit index).
+    // The i-th item must describe the i-th document of the corpus that is ranked with the index.
+    pub fn from_document_stats<'a, I>(document_stats: I) -> FieldedIndex
+    where
+        I: Iterator<Item = &'a DocumentStats>,
+    {
+        let mut postings: HashMap<String, Vec<FieldedPosting>> = HashMap::new();
+        let mut field_lens = Vec::new();
+        for (doc_id, stats) in document_stats.enumerate() {
+            let mut doc_postings: HashMap<&String, [usize; FIELDS.len()]> = HashMap::new();
+            for field in FIELDS {
+                for (term, term_freq) in stats.field(field).term_freqs.iter() {
+                    doc_postings.entry(term).or_default()[field.index()] = *term_freq;
+                }
             }
+            for (term, term_freqs) in doc_postings {
+                postings
+                    .entry(term.clone())
+                    .or_default()
+                    .push(FieldedPosting { doc_id, term_freqs });
+            }
+            field_lens.push(FIELDS.map(|field| stats.field(field).doc_len));
+        }
+        let avg_field_lens = FIELDS.map(|field| {
+            if field_lens.is_empty() {
+                0.0
+            } else {
+                field_lens
+                    .iter()
+                    .map(|lens| lens[field.index()])
+                    .sum::<usize>() as f64
+                    / field_lens.len() as f64
+            }
+        });
+        FieldedIndex {
+            postings,
+            field_lens,
+            avg_field_lens,
         }
-        let q_i_doc = q_i_doc as f64;
-        let num = q_i_doc * (k1 + 1.0);
-        let denom = q_i_doc + k1 * (1.0 - b + b * doc_terms.len() as f64 / avg_doc_len);
+    }
+
+    pub fn num_docs(&self) -> usize {
+        self.field_lens.len()
+    }
+
+    // the number of documents that contain the term in any field
+    pub fn doc_freq(&self, term: &str) -> usize {
+        self.postings.get(term).map_or(0, |postings| postings.len())
+    }
 
-        score += idf * num / denom;
+    pub fn idf(&self, term: &str) -> f64 {
+        self.idf_of_doc_freq(self.doc_freq(term))
+    }
+
+    fn idf_of_doc_freq(&self, doc_freq: usize) -> f64 {
+        let n = self.num_docs() as f64;
+        let n_q = doc_freq as f64;
+        ((n - n_q + 0.5) / (n_q + 0.5) + 1.0).ln()
     }
-    score
 }
 
-impl<T, U> Ranker<T, U> for BM25Ranker<'_>
-where
-    U: Display + Clone,
-    T: Display + Clone,
-{
-    fn rank(
+impl BM25FRanker {
+    pub fn new() -> BM25FRanker {
+        BM25FRanker::builder().build()
+    }
+
+    pub fn builder() -> BM25FRankerBuilder {
+        BM25FRankerBuilder::new()
+    }
+
+    // The terms of every word of the query, analyzed per field, for the fields that count. E.g.
+    // "parseHeaders" is "parsehead" in the title but "pars", "header" and "parsehead" in the code.
+    // Stopwords have no terms in any field and are left out.
+    fn query_words(&self, query: &str) -> Vec<[Vec<String>; FIELDS.len()]> {
+        query
+            .split_whitespace()
+            .map(|word| {
+                FIELDS.map(|field| {
+                    if self.boosts.get(field) <= 0.0 {
+                        return Vec::new();
+                    }
+                    let mut terms: Vec<String> = Vec::new();
+                    for term in field.analyzer().split(word) {
+                        if !terms.contains(&term) {
+                            terms.push(term);
+                        }
+                    }
+                    terms
+                })
+            })
+            .filter(|field_terms| field_terms.iter().any(|terms| !terms.is_empty()))
+            .collect()
+    }
+
+    // Every word of the query is scored once: its frequency in a field is the mean frequency of
+    // its terms in that field, and the document frequency counts the documents that contain any
+    // of its terms in a field that counts.
+    pub fn rank_top_k<T, U>(
         &self,
         query: T,
-        corpus: Vec<U>,
-    ) -> Result<Vec<RankingResult<U>>, Box<dyn std::error::Error>> {
-        let mut total_doc_len = 0;
-        for doc in corpus.iter() {
-            total_doc_len += self.splitter.split(doc.to_string().as_str()).len();
-        }
-        let avg_doc_len = total_doc_len as f64 / corpus.len() as f64;
-        let mut heap = BinaryHeap::<RankingResult<U>>::new();
-        let query_terms = self.splitter.split(query.to_string().as_str());
-        for doc in corpus.iter() {
-            let score = doc_score(
-                doc.clone(),
-                &query_terms,
-                &corpus,
-                self.splitter,
-                avg_doc_len,
-                self.k1,
-                self.b,
-            );
-            heap.push(RankingResult::<U> {
-                score,
-                item: doc.clone(),
-            });
-        }
-        let mut ranked_results = Vec::<RankingResult<U>>::with_capacity(heap.len());
-        while let Some(result) = heap.pop() {
-            ranked_results.push(result);
+        index: &FieldedIndex,
+        corpus: &[U],
+        k: usize,
+    ) -> Vec<RankingResult<U>>
+    where
+        T: Display,
+        U: Display + Clone,
+    {
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+        for field_terms in self.query_words(query.to_string().as_str()) {
+            let mut term_freqs: HashMap<usize, [f64; FIELDS.len()]> = HashMap::new();
+            for field in FIELDS {
+                let i = field.index();
+                let terms = &field_terms[i];
+                for term in terms.iter() {
+                    for posting in index.postings.get(term).into_iter().flatten() {
+                        if posting.term_freqs[i] > 0 {
+                            term_freqs.entry(posting.doc_id).or_default()[i] +=
+                                posting.term_freqs[i] as f64 / terms.len() as f64;
+                        }
+                    }
+                }
+            }
+            let idf = index.idf_of_doc_freq(term_freqs.len());
+            for (doc_id, term_freqs) in term_freqs {
+                let field_lens = &index.field_lens[doc_id];
+                let mut weighted_term_freq = 0.0;
+                for field in FIELDS {
+                    let i = field.index();
+                    if term_freqs[i] == 0.0 {
+                        continue;
+                    }
+                    let norm = if index.avg_field_lens[i] > 0.0 {
+                        1.0 - self.b[i] + self.b[i] * field_lens[i] as f64 / index.avg_field_lens[i]
+                    } else {
+                        1.0
+                    };
+                    weighted_term_freq += self.boosts.get(field) * term_freqs[i] / norm;
+                }
+                *scores.entry(doc_id).or_insert(0.0) +=
+                    idf * weighted_term_freq / (self.k1 + weighted_term_freq);
+            }
         }
-        Ok(ranked_results)
+        top_k(scores, corpus, k)
+    }
+}
+
+impl Default for BM25FRanker {
+    fn default() -> Self {
+        BM25FRanker::new()
     }
 }
 
-fn idf<T>(q_i: &str, corpus: &Vec<T>) -> f64
+impl<T, U> Ranker<T, U> for BM25FRanker
 where
+    U: Display + Clone + Fielded,
     T: Display + Clone,
 {
-    let n = corpus.len() as f64;
-    let mut n_q = 0;
-    for doc in corpus.iter() {
-        if doc.to_string().contains(q_i) {
-            n_q += 1;
-        }
+    fn rank(&self, query: T, corpus: Vec<U>) -> Result<Vec<RankingResult<U>>, GitmError> {
+        let index = FieldedIndex::build(&corpus);
+        Ok(self.rank_top_k(query, &index, &corpus, corpus.len()))
     }
-    let n_q = n_q as f64;
-    ((n - n_q + 0.5) / (n_q + 0.5) + 1.0).ln()
 }
 
-pub struct BM25Retriever {
-    ranker: BM25Ranker<'static>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Document;
 
-pub struct BM25RetrieverBuilder {
-    ranker: Option<BM25Ranker<'static>>,
-}
+    fn index(documents: &[Document]) -> FieldedIndex {
+        let document_stats: Vec<DocumentStats> =
+            documents.iter().map(|document| document.stats()).collect();
+        FieldedIndex::from_document_stats(document_stats.iter())
+    }
 
-impl BM25Retriever {
-    pub fn new() -> Self {
-        Self {
-            ranker: BM25Ranker::builder().build(),
-        }
+    fn rank(ranker: &BM25FRanker, query: &str, documents: &[Document]) -> Vec<(usize, f64)> {
+        let corpus: Vec<usize> = (0..documents.len()).collect();
+        ranker
+            .rank_top_k(query, &index(documents), &corpus, documents.len())
+            .into_iter()
+            .map(|result| (result.item, result.score))
+            .collect()
     }
 
-    pub fn builder() -> BM25RetrieverBuilder {
-        BM25RetrieverBuilder::new()
+    fn title(title: &str) -> Document {
+        Document {
+            title: title.to_string(),
+            ..Document::default()
+        }
     }
-}
 
-impl BM25RetrieverBuilder {
-    pub fn new() -> Self {
-        Self { ranker: None }
+    #[test]
+    fn test_word_is_scored_once_across_fields() {
+        // "parseHeaders" is one term in the title but three in the code
+        let documents = vec![
+            Document {
+                title: String::from("parseHeaders"),
+                code: String::from("fn parseHeaders() {}"),
+                paths: vec![String::from("src/parse_headers.rs")],
+                ..Document::default()
+            },
+            title("Update the readme"),
+        ];
+        let results = rank(&BM25FRanker::new(), "parseHeaders", &documents);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, 0);
+        // the saturated frequency of a single word stays below 1, so its score below its idf
+        let idf = index(&documents).idf_of_doc_freq(1);
+        assert!(results[0].1 < idf, "{} >= {}", results[0].1, idf);
     }
 
-    pub fn ranker(mut self, ranker: BM25Ranker<'static>) -> Self {
-        self.ranker = Some(ranker);
-        self
+    #[test]
+    fn test_word_with_several_terms() {
+        let documents = vec![
+            title("Fix the http client"),
+            title("Fix http redirects"),
+            title("Update the readme"),
+        ];
+        let results = rank(&BM25FRanker::new(), "http_client", &documents);
+        assert_eq!(
+            results.iter().map(|(i, _)| *i).collect::<Vec<usize>>(),
+            vec![0, 1]
+        );
     }
 
-    pub fn build(self) -> BM25Retriever {
-        BM25Retriever {
-            ranker: match self.ranker {
-                Some(ranker) => ranker,
-                None => BM25Ranker::builder().build(),
+    #[test]
+    fn test_field_boosts() {
+        let documents = vec![
+            Document {
+                body: String::from("the tokenizer"),
+                ..Document::default()
             },
-        }
+            title("Rewrite the tokenizer"),
+            Document {
+                code: String::from("let tokenizer = Tokenizer::new();"),
+                ..Document::default()
+            },
+        ];
+        let results = rank(&BM25FRanker::new(), "tokenizer", &documents);
+        assert_eq!(results[0].0, 1);
+        // a field without a boost is not searched at all
+        let ranker = BM25FRanker::builder()
+            .boosts(FieldBoosts {
+                code: 0.0,
+                ..FieldBoosts::default()
+            })
+            .build();
+        let results = rank(&ranker, "tokenizer", &documents);
+        assert!(results.iter().all(|(i, _)| *i != 2));
     }
-}
 
-impl<T, U> Retriever<T, U> for BM25Retriever
-where
-    T: Display + Clone,
-    U: Display + Clone,
-{
-    fn retrieve(
-        &self,
-        query: T,
-        store: Store<U>,
-        max_num_results: usize,
-    ) -> Result<Vec<U>, Box<dyn std::error::Error>> {
-        let ranked_results = self.ranker.rank(query, store.data)?;
-        let mut results = Vec::with_capacity(ranked_results.len());
-        for result in ranked_results {
-            if results.len() >= max_num_results {
-                break;
-            }
-            results.push(result.item);
-        }
-        Ok(results)
+    #[test]
+    fn test_stopwords_are_not_scored() {
+        let documents = vec![title("The parser"), title("The lexer")];
+        assert!(rank(&BM25FRanker::new(), "the", &documents).is_empty());
+        assert_eq!(rank(&BM25FRanker::new(), "the lexer", &documents).len(), 1);
     }
 }
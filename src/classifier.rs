@@ -1,5 +1,5 @@
 use crate::llm::{
-    ChatError, ChatModel, Function, Message, Parameters, Property, Role, Tool, ToolCall,
+    ChatError, ChatProvider, Function, Message, Parameters, Property, Role, Tool, ToolCall,
 };
 use crate::prompts::{BASE_CONTEXT_PROMPT, BINARY_CLASSIFICATION_SYSTEM_PROMPT};
 use serde::{Deserialize, Serialize};
@@ -19,16 +19,22 @@ pub struct BinaryClassificationResult<T> {
     pub content: Option<T>,
 }
 
-pub struct LLMBinaryClassifierContext {
-    model: ChatModel,
+pub struct LLMBinaryClassifierContext<P>
+where
+    P: ChatProvider,
+{
+    model: P,
     system_prompt: String,
     instruction: String,
     result_properties: Vec<(String, Property)>,
 }
 
-impl LLMBinaryClassifierContext {
+impl<P> LLMBinaryClassifierContext<P>
+where
+    P: ChatProvider,
+{
     pub fn new(
-        model: ChatModel,
+        model: P,
         instruction: String,
         additional_information: String,
         result_properties: Vec<(String, Property)>,
@@ -50,7 +56,7 @@ impl LLMBinaryClassifierContext {
         }
     }
 
-    pub fn builder(model: ChatModel, instruction: String) -> LLMBinaryClassifierContextBuilder {
+    pub fn builder(model: P, instruction: String) -> LLMBinaryClassifierContextBuilder<P> {
         LLMBinaryClassifierContextBuilder::new(model, instruction)
     }
 
@@ -104,10 +110,16 @@ impl LLMBinaryClassifierContext {
         let response = self.model.chat(messages, Some(vec![tool]), 0.0).await;
         match response {
             Ok(response) => {
-                let choice = &response.choices[0];
-                let x = choice.message.clone();
-                let tool_call = x.tool_calls.unwrap()[0].clone();
-                Ok(tool_call)
+                // not every provider enforces tool use, so a plain text answer is an error here
+                let tool_call = response
+                    .choices
+                    .into_iter()
+                    .next()
+                    .and_then(|choice| choice.message.tool_calls)
+                    .and_then(|tool_calls| tool_calls.into_iter().next());
+                tool_call.ok_or_else(|| ChatError {
+                    message: String::from("The model did not return a classification"),
+                })
             }
             Err(e) => {
                 return Err(e);
@@ -116,15 +128,21 @@ impl LLMBinaryClassifierContext {
     }
 }
 
-pub struct LLMBinaryClassifierContextBuilder {
-    model: ChatModel,
+pub struct LLMBinaryClassifierContextBuilder<P>
+where
+    P: ChatProvider,
+{
+    model: P,
     instruction: String,
     additional_information: String,
     result_properties: Vec<(String, Property)>,
 }
 
-impl LLMBinaryClassifierContextBuilder {
-    fn new(model: ChatModel, instruction: String) -> Self {
+impl<P> LLMBinaryClassifierContextBuilder<P>
+where
+    P: ChatProvider,
+{
+    fn new(model: P, instruction: String) -> Self {
         Self {
             model,
             instruction,
@@ -143,7 +161,7 @@ impl LLMBinaryClassifierContextBuilder {
         self
     }
 
-    pub fn build(self) -> LLMBinaryClassifierContext {
+    pub fn build(self) -> LLMBinaryClassifierContext<P> {
         LLMBinaryClassifierContext::new(
             self.model,
             self.instruction,
@@ -0,0 +1,812 @@
+use crate::git::DateRange;
+use crate::utils::split_query_words;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use std::collections::HashMap;
+use std::ops::Range;
+
+const MONTH_NAMES: [[&str; 2]; 12] = [
+    ["january", "jan"],
+    ["february", "feb"],
+    ["march", "mar"],
+    ["april", "apr"],
+    ["may", "may"],
+    ["june", "jun"],
+    ["july", "jul"],
+    ["august", "aug"],
+    ["september", "sep"],
+    ["october", "oct"],
+    ["november", "nov"],
+    ["december", "dec"],
+];
+
+// A mentioned point in time as the range of instants that it covers (both ends inclusive), e.g.
+// the whole day for "2023-05-01". Relative mentions such as "3 days ago" start and end at the same
+// instant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl Span {
+    fn instant(datetime: DateTime<Utc>) -> Span {
+        Span {
+            start: datetime,
+            end: datetime,
+        }
+    }
+
+    pub fn is_instant(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+// How a date mention relates to the range that the user wants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Relation {
+    Since,
+    After,
+    Before,
+    Until,
+    During,
+}
+
+// Parses a date on its own, e.g. a value extracted from the query by an LLM. Accepts RFC 3339 and
+// RFC 2822 timestamps, ISO dates ("2023-05-01", "2023-05", "2023"), month names ("may 2023") and
+// relative expressions ("yesterday", "3 days ago", "last week"). Dates and times without an
+// offset are in the timezone of `now`.
+pub fn parse_date<Tz>(s: &str, now: &DateTime<Tz>) -> Option<Span>
+where
+    Tz: TimeZone,
+{
+    let s = s.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Some(Span::instant(datetime.with_timezone(&Utc)));
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc2822(s) {
+        return Some(Span::instant(datetime.with_timezone(&Utc)));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(s, format) {
+            return localize(&now.timezone(), naive).map(Span::instant);
+        }
+    }
+    let words = split_query_words(s);
+    let words: Vec<&str> = words.iter().map(|word| word.as_str()).collect();
+    match parse_span(&words, now, &HashMap::new(), true) {
+        Some((span, n)) if n == words.len() => Some(span),
+        _ => None,
+    }
+}
+
+// Finds the date range that a query asks for, e.g. "last week", "since 2023-05-01", "before v2.0"
+// (with the dates of the repository's tags in `tags`, keyed by lowercase name), "in 2023",
+// "between 2023-01-01 and 2023-03-01" or "from at least a week ago". Returns None if the query
+// mentions no dates.
+pub fn find_date_range<Tz>(
+    query: &str,
+    now: &DateTime<Tz>,
+    tags: &HashMap<String, DateTime<Utc>>,
+) -> Option<DateRange>
+where
+    Tz: TimeZone,
+{
+    find_date_phrases(query, now, tags).0
+}
+
+// Like `find_date_range`, but also returns the phrases that mention the dates as ranges of word
+// indices into `split_query_words(query)`, so that they can be removed from the query.
+pub fn find_date_phrases<Tz>(
+    query: &str,
+    now: &DateTime<Tz>,
+    tags: &HashMap<String, DateTime<Utc>>,
+) -> (Option<DateRange>, Vec<Range<usize>>)
+where
+    Tz: TimeZone,
+{
+    let mut phrases: Vec<Range<usize>> = Vec::new();
+    let words = split_query_words(query);
+    // e.g. "yesterday's fix"
+    let words: Vec<&str> = words
+        .iter()
+        .map(|word| word.strip_suffix("'s").unwrap_or(word))
+        .collect();
+    let now_utc = now.with_timezone(&Utc);
+    let mut since: Option<DateTime<Utc>> = None;
+    let mut until: Option<DateTime<Utc>> = None;
+    let mut i = 0;
+    while i < words.len() {
+        let (mut relation, mut j) = match (words[i], words.get(i + 1)) {
+            ("newer", Some(&"than")) => (Relation::Since, i + 2),
+            ("older", Some(&"than")) | ("prior", Some(&"to")) => (Relation::Before, i + 2),
+            ("since" | "from" | "within", _) => (Relation::Since, i + 1),
+            ("after", _) => (Relation::After, i + 1),
+            ("before", _) => (Relation::Before, i + 1),
+            ("until" | "till" | "through", _) => (Relation::Until, i + 1),
+            ("in" | "on" | "during", _) => (Relation::During, i + 1),
+            ("between", _) => {
+                // "between 2023-01-01 and 2023-03-01"
+                let parsed =
+                    parse_span(&words[i + 1..], now, tags, true).and_then(|(start_span, n)| {
+                        match words.get(i + 1 + n) {
+                            Some(&"and") => parse_span(&words[i + 2 + n..], now, tags, true)
+                                .map(|(end_span, m)| (start_span, end_span, n + m + 1)),
+                            _ => None,
+                        }
+                    });
+                match parsed {
+                    Some((start_span, end_span, n)) => {
+                        since = Some(start_span.start);
+                        until = Some(end_span.end);
+                        phrases.push(i..i + n + 1);
+                        i += n + 1;
+                    }
+                    None => i += 1,
+                }
+                continue;
+            }
+            _ => {
+                // a standalone mention such as "yesterday", "last week" or "3 days ago"
+                match parse_span(&words[i..], now, tags, false) {
+                    Some((span, n)) => {
+                        since = Some(span.start);
+                        if !span.is_instant() && span.end < now_utc {
+                            until = Some(span.end);
+                        }
+                        phrases.push(i..i + n);
+                        i += n;
+                    }
+                    None => i += 1,
+                }
+                continue;
+            }
+        };
+        if words.get(j) == Some(&"the") {
+            j += 1;
+        }
+        // "from at least a week ago" means older than a week
+        match (words.get(j), words.get(j + 1)) {
+            (Some(&"at"), Some(&"least")) | (Some(&"more"), Some(&"than")) => {
+                relation = Relation::Before;
+                j += 2;
+            }
+            (Some(&"over"), _) => {
+                relation = Relation::Before;
+                j += 1;
+            }
+            _ => {}
+        }
+        // "retry in 5 minutes" is in the future, not a range of the past
+        if relation == Relation::During && is_bare_duration(&words[j.min(words.len())..]) {
+            i += 1;
+            continue;
+        }
+        let (span, n) = match parse_span(&words[j.min(words.len())..], now, tags, true) {
+            Some(parsed) => parsed,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+        match relation {
+            Relation::Since => since = Some(span.start),
+            Relation::After if span.is_instant() => since = Some(span.end),
+            Relation::After => since = Some(span.end + Duration::seconds(1)),
+            Relation::Before if span.is_instant() => until = Some(span.start),
+            Relation::Before => until = Some(span.start - Duration::seconds(1)),
+            Relation::Until => until = Some(span.end),
+            Relation::During => {
+                since = Some(span.start);
+                if span.end < now_utc {
+                    until = Some(span.end);
+                }
+            }
+        }
+        phrases.push(i..j + n);
+        i = j + n;
+    }
+    match (since, until) {
+        (None, None) => (None, phrases),
+        date_range => (Some(date_range), phrases),
+    }
+}
+
+// Parses a point in time at the start of `words`, returning it with the number of words that it
+// spans. Bare durations ("2 weeks"), years ("2023") and month names without a year are only
+// accepted after a preposition (`after_preposition`), e.g. "older than 2 weeks", "in 2023" or "in
+// march", as they are too ambiguous on their own (e.g. "error 1999").
+pub fn parse_span<Tz>(
+    words: &[&str],
+    now: &DateTime<Tz>,
+    tags: &HashMap<String, DateTime<Utc>>,
+    after_preposition: bool,
+) -> Option<(Span, usize)>
+where
+    Tz: TimeZone,
+{
+    let tz = now.timezone();
+    let now_utc = now.with_timezone(&Utc);
+    let today = now.date_naive();
+    let first = *words.first()?;
+    match first {
+        // too common a word to be a date mention on its own ("now fails")
+        "now" if after_preposition => return Some((Span::instant(now_utc), 1)),
+        "today" => return Some((day_span(&tz, today, today)?, 1)),
+        "yesterday" => {
+            let yesterday = today.pred_opt()?;
+            return Some((day_span(&tz, yesterday, yesterday)?, 1));
+        }
+        "this" => {
+            // the current calendar period up to now
+            let first_day = match words.get(1)?.trim_end_matches('s') {
+                "day" => today,
+                "week" => today - Duration::days(today.weekday().num_days_from_monday() as i64),
+                "month" => today.with_day(1)?,
+                "year" => NaiveDate::from_ymd_opt(today.year(), 1, 1)?,
+                _ => return None,
+            };
+            let start = localize(&tz, first_day.and_time(NaiveTime::MIN))?;
+            return Some((
+                Span {
+                    start,
+                    end: now_utc,
+                },
+                2,
+            ));
+        }
+        "last" | "past" => {
+            let (duration, n) = parse_duration(&words[1..], true)?;
+            return Some((
+                Span {
+                    start: now_utc - duration,
+                    end: now_utc,
+                },
+                n + 1,
+            ));
+        }
+        _ => {}
+    }
+    if let Some((first_day, last_day, n)) = parse_calendar_period(words, today, after_preposition) {
+        return Some((day_span(&tz, first_day, last_day)?, n));
+    }
+    if let Some(date) = tags.get(first) {
+        return Some((Span::instant(*date), 1));
+    }
+    let (duration, n) = parse_duration(words, false)?;
+    if words.get(n) == Some(&"ago") {
+        return Some((Span::instant(now_utc - duration), n + 1));
+    }
+    if after_preposition {
+        return Some((Span::instant(now_utc - duration), n));
+    }
+    None
+}
+
+// Parses a calendar date, month or year into its first and last day.
+fn parse_calendar_period(
+    words: &[&str],
+    today: NaiveDate,
+    after_preposition: bool,
+) -> Option<(NaiveDate, NaiveDate, usize)> {
+    let first = *words.first()?;
+    for format in ["%Y-%m-%d", "%Y/%m/%d"] {
+        if let Ok(date) = NaiveDate::parse_from_str(first, format) {
+            return Some((date, date, 1));
+        }
+    }
+    if let Some((year, month)) = first.split_once('-') {
+        if let (Some(year), Ok(month)) = (parse_year(year), month.parse::<u32>()) {
+            let (first_day, last_day) = month_period(year, month)?;
+            return Some((first_day, last_day, 1));
+        }
+    }
+    if let Some(year) = parse_year(first).filter(|_| after_preposition) {
+        return Some((
+            NaiveDate::from_ymd_opt(year, 1, 1)?,
+            NaiveDate::from_ymd_opt(year, 12, 31)?,
+            1,
+        ));
+    }
+    let month = MONTH_NAMES
+        .iter()
+        .position(|names| names.contains(&first))? as u32
+        + 1;
+    if let Some(year) = words.get(1).and_then(|word| parse_year(word)) {
+        let (first_day, last_day) = month_period(year, month)?;
+        return Some((first_day, last_day, 2));
+    }
+    if !after_preposition {
+        return None;
+    }
+    // the most recent such month, e.g. "in december" in January means last December
+    let year = if month <= today.month() {
+        today.year()
+    } else {
+        today.year() - 1
+    };
+    let (first_day, last_day) = month_period(year, month)?;
+    Some((first_day, last_day, 1))
+}
+
+// Parses e.g. "3 days", "a week", "two months" or, if the count is optional, "week".
+fn parse_duration(words: &[&str], count_is_optional: bool) -> Option<(Duration, usize)> {
+    let (count, n) = match words.first().and_then(|word| parse_count(word)) {
+        Some(count) => (count, 1),
+        None if count_is_optional => (1, 0),
+        None => return None,
+    };
+    let unit = match words.get(n)?.trim_end_matches('s') {
+        "minute" => Duration::minutes(1),
+        "hour" => Duration::hours(1),
+        "day" => Duration::days(1),
+        "week" => Duration::weeks(1),
+        "month" => Duration::days(30),
+        "year" => Duration::days(365),
+        _ => return None,
+    };
+    Some((unit * count, n + 1))
+}
+
+// e.g. "5 minutes" but not "5 minutes ago"
+fn is_bare_duration(words: &[&str]) -> bool {
+    matches!(parse_duration(words, false), Some((_, n)) if words.get(n) != Some(&"ago"))
+}
+
+fn parse_count(word: &str) -> Option<i32> {
+    match word {
+        "a" | "an" | "one" => Some(1),
+        "two" | "couple" => Some(2),
+        "three" | "few" => Some(3),
+        "four" => Some(4),
+        "five" => Some(5),
+        "six" => Some(6),
+        "seven" => Some(7),
+        "eight" => Some(8),
+        "nine" => Some(9),
+        "ten" => Some(10),
+        _ => word.parse::<i32>().ok().filter(|count| *count > 0),
+    }
+}
+
+fn parse_year(word: &str) -> Option<i32> {
+    if word.len() != 4 {
+        return None;
+    }
+    word.parse::<i32>()
+        .ok()
+        .filter(|year| (1970..=2100).contains(year))
+}
+
+fn month_period(year: i32, month: u32) -> Option<(NaiveDate, NaiveDate)> {
+    let first_day = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let next_month = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)?
+    };
+    Some((first_day, next_month.pred_opt()?))
+}
+
+// From the start of the first day to the end of the last day, in the given timezone.
+fn day_span<Tz>(tz: &Tz, first_day: NaiveDate, last_day: NaiveDate) -> Option<Span>
+where
+    Tz: TimeZone,
+{
+    Some(Span {
+        start: localize(tz, first_day.and_time(NaiveTime::MIN))?,
+        end: localize(tz, last_day.succ_opt()?.and_time(NaiveTime::MIN))? - Duration::seconds(1),
+    })
+}
+
+fn localize<Tz>(tz: &Tz, naive: NaiveDateTime) -> Option<DateTime<Utc>>
+where
+    Tz: TimeZone,
+{
+    tz.from_local_datetime(&naive)
+        .earliest()
+        // the local time does not exist as the clocks were set forward (e.g. midnight on a daylight
+        // saving time switch in some zones)
+        .or_else(|| {
+            tz.from_local_datetime(&(naive + Duration::hours(1)))
+                .earliest()
+        })
+        .map(|datetime| datetime.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+    use chrono_tz::{America, Pacific};
+
+    // a Saturday
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 15, 12, 0, 0).unwrap()
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, min: u32, sec: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, min, sec)
+            .unwrap()
+    }
+
+    fn span(start: DateTime<Utc>, end: DateTime<Utc>) -> Option<Span> {
+        Some(Span { start, end })
+    }
+
+    fn find(query: &str) -> Option<DateRange> {
+        find_date_range(query, &now(), &HashMap::new())
+    }
+
+    fn tags() -> HashMap<String, DateTime<Utc>> {
+        HashMap::from([
+            ("v1.0".to_string(), utc(2023, 9, 1, 8, 0, 0)),
+            ("v2.0".to_string(), utc(2024, 1, 10, 8, 0, 0)),
+        ])
+    }
+
+    #[test]
+    fn test_parse_date_iso() {
+        assert_eq!(
+            parse_date("2023-05-01", &now()),
+            span(utc(2023, 5, 1, 0, 0, 0), utc(2023, 5, 1, 23, 59, 59))
+        );
+        assert_eq!(
+            parse_date("2023/05/01", &now()),
+            span(utc(2023, 5, 1, 0, 0, 0), utc(2023, 5, 1, 23, 59, 59))
+        );
+        assert_eq!(
+            parse_date("2023-02", &now()),
+            span(utc(2023, 2, 1, 0, 0, 0), utc(2023, 2, 28, 23, 59, 59))
+        );
+        assert_eq!(
+            parse_date("2023", &now()),
+            span(utc(2023, 1, 1, 0, 0, 0), utc(2023, 12, 31, 23, 59, 59))
+        );
+        assert_eq!(
+            parse_date("May 2023", &now()),
+            span(utc(2023, 5, 1, 0, 0, 0), utc(2023, 5, 31, 23, 59, 59))
+        );
+        assert_eq!(
+            parse_date("2023-05-01T10:30:00+02:00", &now()),
+            Some(Span::instant(utc(2023, 5, 1, 8, 30, 0)))
+        );
+        assert_eq!(
+            parse_date("2023-05-01 10:30", &now()),
+            Some(Span::instant(utc(2023, 5, 1, 10, 30, 0)))
+        );
+    }
+
+    #[test]
+    fn test_parse_date_relative() {
+        assert_eq!(
+            parse_date("today", &now()),
+            span(utc(2024, 6, 15, 0, 0, 0), utc(2024, 6, 15, 23, 59, 59))
+        );
+        assert_eq!(
+            parse_date("yesterday", &now()),
+            span(utc(2024, 6, 14, 0, 0, 0), utc(2024, 6, 14, 23, 59, 59))
+        );
+        assert_eq!(
+            parse_date("3 days ago", &now()),
+            Some(Span::instant(now() - Duration::days(3)))
+        );
+        assert_eq!(
+            parse_date("two weeks ago", &now()),
+            Some(Span::instant(now() - Duration::weeks(2)))
+        );
+        assert_eq!(
+            parse_date("last week", &now()),
+            span(now() - Duration::weeks(1), now())
+        );
+        assert_eq!(
+            parse_date("past 2 months", &now()),
+            span(now() - Duration::days(60), now())
+        );
+        assert_eq!(
+            parse_date("this week", &now()),
+            span(utc(2024, 6, 10, 0, 0, 0), now())
+        );
+        assert_eq!(
+            parse_date("this year", &now()),
+            span(utc(2024, 1, 1, 0, 0, 0), now())
+        );
+        // the most recent such month
+        assert_eq!(
+            parse_date("march", &now()),
+            span(utc(2024, 3, 1, 0, 0, 0), utc(2024, 3, 31, 23, 59, 59))
+        );
+        assert_eq!(
+            parse_date("december", &now()),
+            span(utc(2023, 12, 1, 0, 0, 0), utc(2023, 12, 31, 23, 59, 59))
+        );
+    }
+
+    #[test]
+    fn test_parse_date_invalid() {
+        assert_eq!(parse_date("", &now()), None);
+        assert_eq!(parse_date("soon", &now()), None);
+        assert_eq!(parse_date("2023-13-01", &now()), None);
+        assert_eq!(parse_date("1800", &now()), None);
+        // all words have to be part of the date
+        assert_eq!(parse_date("yesterday morning", &now()), None);
+    }
+
+    #[test]
+    fn test_parse_date_in_timezone_of_now() {
+        let now = now().with_timezone(&FixedOffset::east_opt(2 * 3600).unwrap());
+        assert_eq!(
+            parse_date("2023-05-01", &now),
+            span(utc(2023, 4, 30, 22, 0, 0), utc(2023, 5, 1, 21, 59, 59))
+        );
+        assert_eq!(
+            parse_date("2023-05-01 10:30", &now),
+            Some(Span::instant(utc(2023, 5, 1, 8, 30, 0)))
+        );
+        // an explicit offset wins
+        assert_eq!(
+            parse_date("2023-05-01T10:30:00Z", &now),
+            Some(Span::instant(utc(2023, 5, 1, 10, 30, 0)))
+        );
+    }
+
+    #[test]
+    fn test_parse_date_on_another_day_than_utc() {
+        // already Sunday in Auckland
+        let now = now().with_timezone(&Pacific::Auckland);
+        assert_eq!(
+            parse_date("today", &now),
+            span(utc(2024, 6, 15, 12, 0, 0), utc(2024, 6, 16, 11, 59, 59))
+        );
+        assert_eq!(
+            parse_date("yesterday", &now),
+            span(utc(2024, 6, 14, 12, 0, 0), utc(2024, 6, 15, 11, 59, 59))
+        );
+    }
+
+    #[test]
+    fn test_parse_date_daylight_saving_time() {
+        let now = now().with_timezone(&America::New_York);
+        // the clocks are set forward, so the day has 23 hours
+        assert_eq!(
+            parse_date("2024-03-10", &now),
+            span(utc(2024, 3, 10, 5, 0, 0), utc(2024, 3, 11, 3, 59, 59))
+        );
+        // and back, so the day has 25 hours
+        assert_eq!(
+            parse_date("2024-11-03", &now),
+            span(utc(2024, 11, 3, 4, 0, 0), utc(2024, 11, 4, 4, 59, 59))
+        );
+        // a local time that happens twice is the earlier one
+        assert_eq!(
+            parse_date("2024-11-03 01:30", &now),
+            Some(Span::instant(utc(2024, 11, 3, 5, 30, 0)))
+        );
+        // midnight did not exist when the clocks were set forward at midnight, so the day starts
+        // an hour later
+        let now = now.with_timezone(&America::Sao_Paulo);
+        assert_eq!(
+            parse_date("2018-11-04", &now),
+            span(utc(2018, 11, 4, 3, 0, 0), utc(2018, 11, 5, 1, 59, 59))
+        );
+    }
+
+    #[test]
+    fn test_find_date_range_iso() {
+        assert_eq!(
+            find("fixes since 2023-05-01"),
+            Some((Some(utc(2023, 5, 1, 0, 0, 0)), None))
+        );
+        assert_eq!(
+            find("fixes after 2023-05-01"),
+            Some((Some(utc(2023, 5, 2, 0, 0, 0)), None))
+        );
+        assert_eq!(
+            find("fixes before 2023-05-01"),
+            Some((None, Some(utc(2023, 4, 30, 23, 59, 59))))
+        );
+        assert_eq!(
+            find("fixes until 2023-05-01"),
+            Some((None, Some(utc(2023, 5, 1, 23, 59, 59))))
+        );
+        assert_eq!(
+            find("fixes on 2023-05-01"),
+            Some((
+                Some(utc(2023, 5, 1, 0, 0, 0)),
+                Some(utc(2023, 5, 1, 23, 59, 59))
+            ))
+        );
+        assert_eq!(
+            find("fixes in may 2023"),
+            Some((
+                Some(utc(2023, 5, 1, 0, 0, 0)),
+                Some(utc(2023, 5, 31, 23, 59, 59))
+            ))
+        );
+        assert_eq!(find("fix the parser"), None);
+    }
+
+    #[test]
+    fn test_find_date_range_relative() {
+        assert_eq!(
+            find("yesterday's fix"),
+            Some((
+                Some(utc(2024, 6, 14, 0, 0, 0)),
+                Some(utc(2024, 6, 14, 23, 59, 59))
+            ))
+        );
+        // a period that is not over yet has no end
+        assert_eq!(
+            find("crashes last week"),
+            Some((Some(now() - Duration::weeks(1)), None))
+        );
+        assert_eq!(
+            find("crashes today"),
+            Some((Some(utc(2024, 6, 15, 0, 0, 0)), None))
+        );
+        assert_eq!(
+            find("crashes within 2 weeks"),
+            Some((Some(now() - Duration::weeks(2)), None))
+        );
+        assert_eq!(
+            find("crashes since 3 days ago"),
+            Some((Some(now() - Duration::days(3)), None))
+        );
+        assert_eq!(
+            find("crashes in march"),
+            Some((
+                Some(utc(2024, 3, 1, 0, 0, 0)),
+                Some(utc(2024, 3, 31, 23, 59, 59))
+            ))
+        );
+        // too ambiguous on their own
+        assert_eq!(find("now fails"), None);
+        assert_eq!(find("2 weeks of work"), None);
+        assert_eq!(find("may fail"), None);
+    }
+
+    #[test]
+    fn test_find_date_range_open_ended() {
+        assert_eq!(
+            find("issues from at least a week ago"),
+            Some((None, Some(now() - Duration::weeks(1))))
+        );
+        assert_eq!(
+            find("issues from more than 3 days ago"),
+            Some((None, Some(now() - Duration::days(3))))
+        );
+        assert_eq!(
+            find("issues older than 2 weeks"),
+            Some((None, Some(now() - Duration::weeks(2))))
+        );
+        assert_eq!(
+            find("issues newer than 2 weeks"),
+            Some((Some(now() - Duration::weeks(2)), None))
+        );
+        assert_eq!(
+            find("issues prior to 2023"),
+            Some((None, Some(utc(2022, 12, 31, 23, 59, 59))))
+        );
+    }
+
+    #[test]
+    fn test_find_date_range_between() {
+        assert_eq!(
+            find("crashes between 2023-01-01 and 2023-03-01"),
+            Some((
+                Some(utc(2023, 1, 1, 0, 0, 0)),
+                Some(utc(2023, 3, 1, 23, 59, 59))
+            ))
+        );
+        assert_eq!(
+            find("between january 2023 and march 2023"),
+            Some((
+                Some(utc(2023, 1, 1, 0, 0, 0)),
+                Some(utc(2023, 3, 31, 23, 59, 59))
+            ))
+        );
+        assert_eq!(find("between the parser and the lexer"), None);
+    }
+
+    #[test]
+    fn test_find_date_range_tags() {
+        let tags = tags();
+        assert_eq!(
+            find_date_range("fixes before v2.0", &now(), &tags),
+            Some((None, Some(utc(2024, 1, 10, 8, 0, 0))))
+        );
+        assert_eq!(
+            find_date_range("fixes since V1.0", &now(), &tags),
+            Some((Some(utc(2023, 9, 1, 8, 0, 0)), None))
+        );
+        assert_eq!(
+            find_date_range("fixes after v1.0 and before v2.0", &now(), &tags),
+            Some((
+                Some(utc(2023, 9, 1, 8, 0, 0)),
+                Some(utc(2024, 1, 10, 8, 0, 0))
+            ))
+        );
+        assert_eq!(
+            find_date_range("between v1.0 and v2.0", &now(), &tags),
+            Some((
+                Some(utc(2023, 9, 1, 8, 0, 0)),
+                Some(utc(2024, 1, 10, 8, 0, 0))
+            ))
+        );
+        assert_eq!(find_date_range("fixes before v3.0", &now(), &tags), None);
+    }
+
+    #[test]
+    fn test_find_date_range_in_timezone_of_now() {
+        let now = now().with_timezone(&America::New_York);
+        assert_eq!(
+            find_date_range("fixes on 2024-03-10", &now, &HashMap::new()),
+            Some((
+                Some(utc(2024, 3, 10, 5, 0, 0)),
+                Some(utc(2024, 3, 11, 3, 59, 59))
+            ))
+        );
+        assert_eq!(
+            find_date_range("fixes before 2024-11-04", &now, &HashMap::new()),
+            Some((None, Some(utc(2024, 11, 4, 4, 59, 59))))
+        );
+    }
+
+    #[test]
+    fn test_find_date_phrases() {
+        let (_, phrases) = find_date_phrases(
+            "bug fixes from last week in the parser",
+            &now(),
+            &HashMap::new(),
+        );
+        assert_eq!(phrases, vec![2..5]);
+        let (_, phrases) = find_date_phrases(
+            "crashes between 2023-01-01 and 2023-03-01 on windows",
+            &now(),
+            &HashMap::new(),
+        );
+        assert_eq!(phrases, vec![1..5]);
+        let (_, phrases) =
+            find_date_phrases("issues from at least a week ago", &now(), &HashMap::new());
+        assert_eq!(phrases, vec![1..7]);
+        let (_, phrases) = find_date_phrases("fixes after v1.0 and before v2.0", &now(), &tags());
+        assert_eq!(phrases, vec![1..3, 4..6]);
+        let (date_range, phrases) =
+            find_date_phrases("retry in 5 minutes", &now(), &HashMap::new());
+        assert_eq!(date_range, None);
+        assert!(phrases.is_empty());
+    }
+
+    #[test]
+    fn test_bare_year_is_not_a_date() {
+        assert_eq!(find("error 1999"), None);
+        assert_eq!(find("issue 2048"), None);
+    }
+
+    #[test]
+    fn test_year_after_preposition() {
+        let range = (
+            Some(utc(2023, 1, 1, 0, 0, 0)),
+            Some(utc(2023, 12, 31, 23, 59, 59)),
+        );
+        assert_eq!(find("fixes in 2023"), Some(range));
+        assert_eq!(find("fixes during 2023"), Some(range));
+        assert_eq!(
+            find("fixes since 2023"),
+            Some((Some(utc(2023, 1, 1, 0, 0, 0)), None))
+        );
+        assert_eq!(
+            find("fixes before 2023"),
+            Some((None, Some(utc(2022, 12, 31, 23, 59, 59))))
+        );
+    }
+
+    #[test]
+    fn test_in_duration_is_not_a_past_range() {
+        assert_eq!(find("retry in 5 minutes"), None);
+        assert_eq!(find("expires in 2 days"), None);
+        assert_eq!(
+            find("in the last 5 minutes"),
+            Some((Some(now() - Duration::minutes(5)), None))
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use crate::analysis::{code_analyzer, text_analyzer};
+use crate::index::TermStats;
+use crate::splitters::Splitter;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+// A part of a document that is ranked with its own weight and length normalization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    Title,
+    Body,
+    Code,
+    Paths,
+}
+
+pub const FIELDS: [Field; 4] = [Field::Title, Field::Body, Field::Code, Field::Paths];
+
+impl Field {
+    // position of the field in per-field arrays
+    pub fn index(self) -> usize {
+        match self {
+            Field::Title => 0,
+            Field::Body => 1,
+            Field::Code => 2,
+            Field::Paths => 3,
+        }
+    }
+
+    // The analyzer that the field is indexed with. Queries are analyzed with the analyzers of all
+    // fields, so that a term only has to match in one of them.
+    pub fn analyzer(self) -> &'static dyn Splitter {
+        match self {
+            Field::Title | Field::Body => text_analyzer(),
+            Field::Code | Field::Paths => code_analyzer(),
+        }
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Field::Title => write!(f, "title"),
+            Field::Body => write!(f, "body"),
+            Field::Code => write!(f, "code"),
+            Field::Paths => write!(f, "paths"),
+        }
+    }
+}
+
+// The searchable text of a commit, issue or pull request by field. Fields that do not apply, such
+// as the code of an issue, are empty.
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    pub title: String,
+    pub body: String,
+    // the lines that a patch adds
+    pub code: String,
+    pub paths: Vec<String>,
+}
+
+impl Document {
+    pub fn text(&self, field: Field) -> String {
+        match field {
+            Field::Title => self.title.clone(),
+            Field::Body => self.body.clone(),
+            Field::Code => self.code.clone(),
+            Field::Paths => self.paths.join("\n"),
+        }
+    }
+
+    pub fn stats(&self) -> DocumentStats {
+        let stats = |field: Field| TermStats::from_text(&self.text(field), field.analyzer());
+        DocumentStats {
+            title: stats(Field::Title),
+            body: stats(Field::Body),
+            code: stats(Field::Code),
+            paths: stats(Field::Paths),
+        }
+    }
+}
+
+// Anything that can be ranked field by field.
+pub trait Fielded {
+    fn document(&self) -> Document;
+}
+
+// The term statistics of every field of a document, which is all that ranking needs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DocumentStats {
+    pub title: TermStats,
+    pub body: TermStats,
+    pub code: TermStats,
+    pub paths: TermStats,
+}
+
+impl DocumentStats {
+    pub fn field(&self, field: Field) -> &TermStats {
+        match field {
+            Field::Title => &self.title,
+            Field::Body => &self.body,
+            Field::Code => &self.code,
+            Field::Paths => &self.paths,
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use crate::error::GitmError;
+use crate::llm::{ChatError, ChatModel};
+use crate::rankers::RankingResult;
+use crate::retrievers::Retriever;
+use crate::store::{Identifiable, Store};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt::Display;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const DEFAULT_EMBEDDING_BATCH_SIZE: usize = 128;
+// keeps long documents (e.g. commits with huge bodies) under the embedding models' input limits
+const MAX_EMBEDDING_INPUT_CHARS: usize = 8000;
+const DEFAULT_HASHING_DIMENSIONS: usize = 512;
+
+pub trait EmbeddingBackend {
+    fn embed(
+        &self,
+        inputs: Vec<String>,
+    ) -> impl Future<Output = Result<Vec<Vec<f32>>, ChatError>> + Send;
+
+    // identifies the backend so that vectors from different models are never mixed in a cache
+    fn name(&self) -> String;
+}
+
+impl EmbeddingBackend for ChatModel {
+    async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, ChatError> {
+        ChatModel::embed(self, inputs).await
+    }
+
+    fn name(&self) -> String {
+        ChatModel::name(self)
+    }
+}
+
+// A local backend that needs no network access: tokens and character trigrams are hashed into a
+// fixed number of dimensions. It captures lexical overlap and spelling variants rather than
+// meaning, but is useful when no embeddings endpoint is available.
+pub struct HashingEmbeddingBackend {
+    dimensions: usize,
+}
+
+impl HashingEmbeddingBackend {
+    pub fn new() -> HashingEmbeddingBackend {
+        HashingEmbeddingBackend {
+            dimensions: DEFAULT_HASHING_DIMENSIONS,
+        }
+    }
+
+    pub fn dimensions(mut self, dimensions: usize) -> HashingEmbeddingBackend {
+        self.dimensions = dimensions;
+        self
+    }
+
+    fn embed_one(&self, input: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimensions];
+        let lowercased = input.to_lowercase();
+        for token in lowercased
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|token| !token.is_empty())
+        {
+            vector[fnv1a(token.as_bytes()) as usize % self.dimensions] += 1.0;
+            let chars: Vec<char> = format!("#{}#", token).chars().collect();
+            for trigram in chars.windows(3) {
+                let trigram: String = trigram.iter().collect();
+                vector[fnv1a(trigram.as_bytes()) as usize % self.dimensions] += 0.5;
+            }
+        }
+        vector
+    }
+}
+
+impl Default for HashingEmbeddingBackend {
+    fn default() -> Self {
+        HashingEmbeddingBackend::new()
+    }
+}
+
+impl EmbeddingBackend for HashingEmbeddingBackend {
+    async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, ChatError> {
+        Ok(inputs.iter().map(|input| self.embed_one(input)).collect())
+    }
+
+    fn name(&self) -> String {
+        format!("hashing-{}", self.dimensions)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedEmbedding {
+    // fingerprint of the embedded text so that edited items (e.g. issues) are re-embedded
+    fingerprint: u64,
+    vector: Vec<f32>,
+}
+
+// Embedding vectors keyed by item id (commit SHA, issue number), optionally persisted to disk.
+pub struct EmbeddingCache {
+    path: Option<PathBuf>,
+    entries: HashMap<String, CachedEmbedding>,
+    dirty: bool,
+}
+
+impl EmbeddingCache {
+    pub fn in_memory() -> EmbeddingCache {
+        EmbeddingCache {
+            path: None,
+            entries: HashMap::new(),
+            dirty: false,
+        }
+    }
+
+    pub fn open(path: PathBuf) -> Result<EmbeddingCache, GitmError> {
+        let mut cache = EmbeddingCache {
+            path: Some(path.clone()),
+            entries: HashMap::new(),
+            dirty: false,
+        };
+        if path.exists() {
+            // a corrupt cache is discarded, the vectors are simply recomputed
+            if let Ok(entries) = serde_json::from_str(&fs::read_to_string(&path)?) {
+                cache.entries = entries;
+            }
+        }
+        Ok(cache)
+    }
+
+    pub fn save(&mut self) -> Result<(), GitmError> {
+        let path = match &self.path {
+            Some(path) if self.dirty => path,
+            _ => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string(&self.entries)?)?;
+        fs::rename(&tmp_path, path)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn get(&self, id: &str, text: &str) -> Option<&Vec<f32>> {
+        self.entries
+            .get(id)
+            .filter(|cached| cached.fingerprint == fnv1a(text.as_bytes()))
+            .map(|cached| &cached.vector)
+    }
+
+    fn insert(&mut self, id: String, text: &str, vector: Vec<f32>) {
+        self.entries.insert(
+            id,
+            CachedEmbedding {
+                fingerprint: fnv1a(text.as_bytes()),
+                vector,
+            },
+        );
+        self.dirty = true;
+    }
+}
+
+pub struct EmbeddingRetriever<B>
+where
+    B: EmbeddingBackend,
+{
+    backend: B,
+    cache: Mutex<EmbeddingCache>,
+    batch_size: usize,
+}
+
+impl<B> EmbeddingRetriever<B>
+where
+    B: EmbeddingBackend + Sync,
+{
+    pub fn new(backend: B, cache: EmbeddingCache) -> EmbeddingRetriever<B> {
+        EmbeddingRetriever {
+            backend,
+            cache: Mutex::new(cache),
+            batch_size: DEFAULT_EMBEDDING_BATCH_SIZE,
+        }
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> EmbeddingRetriever<B> {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn save_cache(&self) -> Result<(), GitmError> {
+        self.cache.lock().unwrap().save()
+    }
+
+    pub async fn rank<T, U>(
+        &self,
+        query: T,
+        store: &Store<U>,
+        max_num_results: usize,
+    ) -> Result<Vec<RankingResult<U>>, GitmError>
+    where
+        T: Display,
+        U: Display + Clone + Identifiable,
+    {
+        let texts: Vec<String> = store
+            .data
+            .iter()
+            .map(|item| truncate(item.to_string()))
+            .collect();
+        let missing: Vec<usize> = {
+            let cache = self.cache.lock().unwrap();
+            (0..store.data.len())
+                .filter(|i| cache.get(&store.data[*i].id(), &texts[*i]).is_none())
+                .collect()
+        };
+        for batch in missing.chunks(self.batch_size) {
+            let inputs = batch.iter().map(|i| texts[*i].clone()).collect();
+            let vectors = self.backend.embed(inputs).await?;
+            if vectors.len() != batch.len() {
+                return Err(GitmError::Llm(ChatError {
+                    message: format!("Expected {} embeddings, got {}", batch.len(), vectors.len()),
+                }));
+            }
+            let mut cache = self.cache.lock().unwrap();
+            for (i, vector) in batch.iter().zip(vectors) {
+                cache.insert(store.data[*i].id(), &texts[*i], vector);
+            }
+        }
+        let query_vector = self
+            .backend
+            .embed(vec![truncate(query.to_string())])
+            .await?
+            .pop()
+            .ok_or_else(|| ChatError {
+                message: String::from("No embedding returned for the query"),
+            })?;
+        let cache = self.cache.lock().unwrap();
+        let mut heap = BinaryHeap::<Reverse<RankingResult<U>>>::with_capacity(max_num_results + 1);
+        for (i, item) in store.data.iter().enumerate() {
+            let vector = match cache.get(&item.id(), &texts[i]) {
+                Some(vector) => vector,
+                None => continue,
+            };
+            let score = cosine_similarity(&query_vector, vector).ok_or_else(|| ChatError {
+                message: format!(
+                    "Expected embeddings with {} dimensions, got {}",
+                    query_vector.len(),
+                    vector.len()
+                ),
+            })?;
+            heap.push(Reverse(RankingResult {
+                score,
+                item: item.clone(),
+            }));
+            if heap.len() > max_num_results {
+                heap.pop();
+            }
+        }
+        let mut ranked_results: Vec<RankingResult<U>> =
+            heap.into_iter().map(|Reverse(result)| result).collect();
+        ranked_results.sort_by(|a, b| b.cmp(a));
+        Ok(ranked_results)
+    }
+}
+
+impl<T, U, B> Retriever<T, U> for EmbeddingRetriever<B>
+where
+    T: Display + Clone + Send,
+    U: Display + Clone + Send + Sync + Identifiable,
+    B: EmbeddingBackend + Sync,
+{
+    async fn retrieve(
+        &self,
+        query: T,
+        store: &Store<U>,
+        max_num_results: usize,
+    ) -> Result<Vec<RankingResult<U>>, GitmError> {
+        self.rank(query, store, max_num_results).await
+    }
+}
+
+// None if the vectors have different lengths, e.g. as they are from different models.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f64> {
+    if a.len() != b.len() {
+        return None;
+    }
+    let mut dot = 0.0;
+    let mut norm_a = 0.0;
+    let mut norm_b = 0.0;
+    for (x, y) in a.iter().zip(b.iter()) {
+        dot += (*x as f64) * (*y as f64);
+        norm_a += (*x as f64) * (*x as f64);
+        norm_b += (*y as f64) * (*y as f64);
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return Some(0.0);
+    }
+    Some(dot / (norm_a.sqrt() * norm_b.sqrt()))
+}
+
+fn truncate(mut s: String) -> String {
+    if s.len() > MAX_EMBEDDING_INPUT_CHARS {
+        let mut end = MAX_EMBEDDING_INPUT_CHARS;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        s.truncate(end);
+    }
+    s
+}
+
+// FNV-1a is used instead of `DefaultHasher` because the hashes are persisted and must be stable
+// across Rust versions.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]), Some(1.0));
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), Some(0.0));
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[-1.0, 0.0]), Some(-1.0));
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), Some(0.0));
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0, 0.0]), None);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::llm::ChatError;
+use std::fmt;
+
+// Everything that can go wrong during a search, by the component that failed so that callers can
+// decide what to skip and the CLI can tell the user what to fix.
+#[derive(Debug)]
+pub enum GitmError {
+    // git is missing, the working directory is not a repository or a git command failed
+    Git(String),
+    // the GitHub API or the gh CLI failed
+    GitHub(String),
+    // the LLM provider failed or returned something that could not be used
+    Llm(ChatError),
+    // output of git, GitHub or a file that could not be parsed
+    Parse(String),
+    // invalid arguments, missing credentials or settings that contradict each other
+    Config(String),
+    // reading or writing the index and caches
+    Io(std::io::Error),
+    // the user interrupted the search
+    Cancelled,
+}
+
+impl GitmError {
+    // The exit code of the CLI, distinct per kind so that scripts can tell failures apart.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            GitmError::Config(_) => 2,
+            GitmError::Git(_) => 3,
+            GitmError::GitHub(_) => 4,
+            GitmError::Llm(_) => 5,
+            GitmError::Parse(_) => 6,
+            GitmError::Io(_) => 7,
+            // as if killed by SIGINT
+            GitmError::Cancelled => 130,
+        }
+    }
+
+    // What the user can do about the failure, if there is anything.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            GitmError::Git(_) => Some("Run gitm inside of a git repository with git installed"),
+            GitmError::GitHub(_) => Some(
+                "Check GITHUB_TOKEN (or log in with `gh auth login`) and that the origin remote points to GitHub, or search commits only",
+            ),
+            GitmError::Llm(_) => Some(
+                "Check the API key and --provider, retry later or search without an LLM with --offline",
+            ),
+            GitmError::Parse(_) | GitmError::Io(_) => {
+                Some("Try again with --rebuild-index in case the index in .git/gitm is corrupt")
+            }
+            GitmError::Config(_) | GitmError::Cancelled => None,
+        }
+    }
+}
+
+impl fmt::Display for GitmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitmError::Git(message) => write!(f, "git error: {}", message),
+            GitmError::GitHub(message) => write!(f, "GitHub error: {}", message),
+            GitmError::Llm(error) => write!(f, "LLM error: {}", error.message),
+            GitmError::Parse(message) => write!(f, "parse error: {}", message),
+            GitmError::Config(message) => write!(f, "{}", message),
+            GitmError::Io(error) => write!(f, "I/O error: {}", error),
+            GitmError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for GitmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GitmError::Llm(error) => Some(error),
+            GitmError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ChatError> for GitmError {
+    fn from(error: ChatError) -> Self {
+        GitmError::Llm(error)
+    }
+}
+
+impl From<std::io::Error> for GitmError {
+    fn from(error: std::io::Error) -> Self {
+        GitmError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for GitmError {
+    fn from(error: serde_json::Error) -> Self {
+        GitmError::Parse(error.to_string())
+    }
+}
+
+impl From<std::string::FromUtf8Error> for GitmError {
+    fn from(error: std::string::FromUtf8Error) -> Self {
+        GitmError::Parse(error.to_string())
+    }
+}
+
+impl From<chrono::ParseError> for GitmError {
+    fn from(error: chrono::ParseError) -> Self {
+        GitmError::Parse(error.to_string())
+    }
+}
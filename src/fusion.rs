@@ -0,0 +1,264 @@
+use crate::rankers::RankingResult;
+use crate::store::Identifiable;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+// The constant from the original reciprocal rank fusion paper (Cormack et al., 2009); it dampens
+// the influence of the very top ranks of any single list.
+pub const DEFAULT_RRF_K: f64 = 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FusionStrategy {
+    // sum of weight / (k + rank) over the lists that contain the item
+    ReciprocalRank,
+    // sum of weight * min-max normalized score over the lists that contain the item
+    WeightedScore,
+}
+
+impl FromStr for FusionStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rrf" => Ok(FusionStrategy::ReciprocalRank),
+            "weighted" => Ok(FusionStrategy::WeightedScore),
+            _ => Err(format!(
+                "unknown fusion strategy: {} (expected \"rrf\" or \"weighted\")",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FusionConfig {
+    pub strategy: FusionStrategy,
+    pub rrf_k: f64,
+    // the BM25F pass over the title, body, code and paths
+    pub keyword_weight: f64,
+    pub semantic_weight: f64,
+}
+
+impl Default for FusionConfig {
+    fn default() -> Self {
+        FusionConfig {
+            strategy: FusionStrategy::ReciprocalRank,
+            rrf_k: DEFAULT_RRF_K,
+            keyword_weight: 1.0,
+            semantic_weight: 1.0,
+        }
+    }
+}
+
+// Merges several ranked lists of the same kind of item into one. Items are matched across lists by
+// `Identifiable::id`; the first occurrence (in list order) is kept as the fused item.
+pub fn fuse<U>(
+    ranked_lists: Vec<(f64, Vec<RankingResult<U>>)>,
+    strategy: FusionStrategy,
+    rrf_k: f64,
+    max_num_results: usize,
+) -> Vec<RankingResult<U>>
+where
+    U: Display + Clone + Identifiable,
+{
+    let mut fused: Vec<RankingResult<U>> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for (weight, ranked_list) in ranked_lists {
+        let (min_score, max_score) = ranked_list
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), result| {
+                (min.min(result.score), max.max(result.score))
+            });
+        for (rank, result) in ranked_list.into_iter().enumerate() {
+            let contribution = match strategy {
+                FusionStrategy::ReciprocalRank => weight / (rrf_k + (rank + 1) as f64),
+                FusionStrategy::WeightedScore => {
+                    if max_score > min_score {
+                        weight * (result.score - min_score) / (max_score - min_score)
+                    } else {
+                        // every item of the list scored the same, so they are all equally good
+                        weight
+                    }
+                }
+            };
+            let id = result.item.id();
+            match positions.get(&id) {
+                Some(position) => fused[*position].score += contribution,
+                None => {
+                    positions.insert(id, fused.len());
+                    fused.push(RankingResult {
+                        score: contribution,
+                        item: result.item,
+                    });
+                }
+            }
+        }
+    }
+    // stable sort so that ties keep the order of the earlier lists
+    fused.sort_by(|a, b| b.score.total_cmp(&a.score));
+    fused.truncate(max_num_results);
+    fused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Item(&'static str);
+
+    impl Display for Item {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl Identifiable for Item {
+        fn id(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    fn ranked(results: &[(&'static str, f64)]) -> Vec<RankingResult<Item>> {
+        results
+            .iter()
+            .map(|(id, score)| RankingResult {
+                score: *score,
+                item: Item(id),
+            })
+            .collect()
+    }
+
+    fn ids(fused: &[RankingResult<Item>]) -> Vec<&'static str> {
+        fused.iter().map(|result| result.item.0).collect()
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_reciprocal_rank_order() {
+        let fused = fuse(
+            vec![
+                (1.0, ranked(&[("a", 9.0), ("b", 5.0), ("c", 1.0)])),
+                (1.0, ranked(&[("b", 0.9), ("c", 0.8), ("d", 0.1)])),
+            ],
+            FusionStrategy::ReciprocalRank,
+            DEFAULT_RRF_K,
+            10,
+        );
+        // items in both lists beat an item that is first in only one of them
+        assert_eq!(ids(&fused), vec!["b", "c", "a", "d"]);
+        assert_close(fused[0].score, 1.0 / 62.0 + 1.0 / 61.0);
+        assert_close(fused[1].score, 1.0 / 63.0 + 1.0 / 62.0);
+        assert_close(fused[2].score, 1.0 / 61.0);
+        assert_close(fused[3].score, 1.0 / 63.0);
+    }
+
+    #[test]
+    fn test_reciprocal_rank_weights_and_k() {
+        let lists = || {
+            vec![
+                (1.0, ranked(&[("a", 1.0), ("b", 0.5)])),
+                (3.0, ranked(&[("b", 1.0), ("a", 0.5)])),
+            ]
+        };
+        let fused = fuse(lists(), FusionStrategy::ReciprocalRank, DEFAULT_RRF_K, 10);
+        assert_eq!(ids(&fused), vec!["b", "a"]);
+        let fused = fuse(lists(), FusionStrategy::ReciprocalRank, 0.0, 10);
+        assert_close(fused[0].score, 1.0 / 2.0 + 3.0 / 1.0);
+        assert_close(fused[1].score, 1.0 / 1.0 + 3.0 / 2.0);
+    }
+
+    #[test]
+    fn test_weighted_score() {
+        let fused = fuse(
+            vec![
+                (1.0, ranked(&[("a", 10.0), ("b", 5.0), ("c", 0.0)])),
+                (2.0, ranked(&[("c", 0.9), ("a", 0.3), ("b", 0.1)])),
+            ],
+            FusionStrategy::WeightedScore,
+            DEFAULT_RRF_K,
+            10,
+        );
+        assert_eq!(ids(&fused), vec!["c", "a", "b"]);
+        assert_close(fused[0].score, 0.0 + 2.0);
+        assert_close(fused[1].score, 1.0 + 2.0 * 0.25);
+        assert_close(fused[2].score, 0.5 + 0.0);
+    }
+
+    #[test]
+    fn test_weighted_score_with_equal_scores() {
+        // min-max normalization would divide by zero
+        let fused = fuse(
+            vec![
+                (1.0, ranked(&[("a", 3.0), ("b", 3.0)])),
+                (0.5, ranked(&[("c", 0.0)])),
+            ],
+            FusionStrategy::WeightedScore,
+            DEFAULT_RRF_K,
+            10,
+        );
+        assert!(fused.iter().all(|result| result.score.is_finite()));
+        assert_eq!(ids(&fused), vec!["a", "b", "c"]);
+        assert_close(fused[0].score, 1.0);
+        assert_close(fused[1].score, 1.0);
+        assert_close(fused[2].score, 0.5);
+    }
+
+    #[test]
+    fn test_items_in_one_list() {
+        for strategy in [
+            FusionStrategy::ReciprocalRank,
+            FusionStrategy::WeightedScore,
+        ] {
+            let fused = fuse(
+                vec![
+                    (1.0, ranked(&[("a", 1.0), ("b", 0.0)])),
+                    (1.0, ranked(&[("c", 1.0), ("a", 0.0)])),
+                    (1.0, Vec::new()),
+                ],
+                strategy,
+                DEFAULT_RRF_K,
+                10,
+            );
+            // every item is kept once, whether it is in one list or both
+            let mut fused_ids = ids(&fused);
+            fused_ids.sort();
+            assert_eq!(fused_ids, vec!["a", "b", "c"]);
+            // a is in both lists, so it is first
+            assert_eq!(fused[0].item, Item("a"));
+        }
+    }
+
+    #[test]
+    fn test_ties_keep_list_order_and_truncate() {
+        let fused = fuse(
+            vec![
+                (1.0, ranked(&[("a", 1.0)])),
+                (1.0, ranked(&[("b", 1.0)])),
+                (1.0, ranked(&[("c", 1.0)])),
+            ],
+            FusionStrategy::ReciprocalRank,
+            DEFAULT_RRF_K,
+            2,
+        );
+        assert_eq!(ids(&fused), vec!["a", "b"]);
+        assert!(fuse::<Item>(
+            Vec::new(),
+            FusionStrategy::ReciprocalRank,
+            DEFAULT_RRF_K,
+            10
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_fusion_strategy_from_str() {
+        assert_eq!("rrf".parse(), Ok(FusionStrategy::ReciprocalRank));
+        assert_eq!("weighted".parse(), Ok(FusionStrategy::WeightedScore));
+        assert!("max".parse::<FusionStrategy>().is_err());
+    }
+}
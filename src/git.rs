@@ -1,11 +1,24 @@
+use crate::authors::Identity;
+use crate::document::{Document, Fielded};
+use crate::error::GitmError;
 use crate::fmt::{colorize_string, indent_string, Color};
+use crate::paths::path_matches;
+use crate::query_syntax::{normalize_text, TextQuery};
+use crate::store::Identifiable;
+use crate::utils::strip_plural;
 use chrono::{DateTime, Utc};
+use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
-use std::hash::{Hash, Hasher};
-use std::process::Command;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
 use unidiff::PatchSet;
 
-pub struct Client;
+#[derive(Debug, Clone, Default)]
+pub struct Client {
+    // the repository that git runs in, the current directory if not set
+    dir: Option<PathBuf>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Commit {
@@ -14,11 +27,21 @@ pub struct Commit {
     pub title: String,
     pub body: String,
     pub sha: String,
-    pub patch_set: PatchSet,
+    // the lines that the patch adds, without the leading "+"
+    pub added_code: String,
+    // the paths of the files that the commit changed, before and after renames
+    pub changed_paths: Vec<String>,
+    // (old path, new path) of the files that the commit renamed
+    pub renames: Vec<(String, String)>,
     pub display_mode: CommitDisplayMode,
 }
 
 impl Commit {
+    // The full patch is not kept in the index, so it is read from git again when it is displayed.
+    pub fn patch_set(&self) -> Result<PatchSet, GitmError> {
+        Client::new().get_patch_set(&self.sha)
+    }
+
     pub fn mock_git_log_fmt(&self) -> String {
         format!(
             "{}
@@ -46,11 +69,29 @@ Date: {}
     }
 }
 
+impl Fielded for Commit {
+    fn document(&self) -> Document {
+        Document {
+            title: self.title.clone(),
+            body: self.body.clone(),
+            code: self.added_code.clone(),
+            paths: self.changed_paths.clone(),
+        }
+    }
+}
+
+impl Identifiable for Commit {
+    fn id(&self) -> String {
+        self.sha.clone()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum CommitDisplayMode {
     Title,
     Body,
     TitleAndBody,
+    // the lines that the patch adds, which are ranked as the code field
     PatchSetAdd,
     PatchSetRemove,
     PatchSetAll,
@@ -62,182 +103,324 @@ impl Display for Commit {
             CommitDisplayMode::Title => write!(f, "{}", self.title),
             CommitDisplayMode::Body => write!(f, "{}", self.body),
             CommitDisplayMode::TitleAndBody => write!(f, "{}\n\n{}", self.title, self.body),
-            CommitDisplayMode::PatchSetAdd => {
-                let mut added_line_content = Vec::<String>::new();
-                for file in self.patch_set.files() {
-                    for hunk in file.hunks() {
-                        for line in hunk.lines() {
-                            if line.is_added() {
-                                added_line_content.push(line.value.clone());
-                            }
-                        }
-                    }
-                }
-                write!(f, "{}", added_line_content.join("\n"))
-            }
+            CommitDisplayMode::PatchSetAdd => write!(f, "{}", self.added_code),
             CommitDisplayMode::PatchSetRemove => {
-                let mut removed_line_content = Vec::<String>::new();
-                for file in self.patch_set.files() {
-                    for hunk in file.hunks() {
-                        for line in hunk.lines() {
-                            if line.is_removed() {
-                                removed_line_content.push(line.value.clone());
-                            }
-                        }
-                    }
-                }
-                write!(f, "{}", removed_line_content.join("\n"))
+                let patch_set = self.patch_set().map_err(|_| std::fmt::Error)?;
+                write!(f, "{}", removed_code(&patch_set))
+            }
+            CommitDisplayMode::PatchSetAll => {
+                let patch_set = self.patch_set().map_err(|_| std::fmt::Error)?;
+                write!(f, "{}", patch_set)
             }
-            CommitDisplayMode::PatchSetAll => write!(f, "{}", self.patch_set),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+// An author as recorded by git or GitHub. People with several names, emails or logins are
+// matched through `authors::Identity`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Author {
     pub name: Option<String>,
+    // the GitHub login
     pub username: Option<String>,
     pub email: Option<String>,
 }
 
-impl PartialEq for Author {
-    fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
-    }
-}
-
-impl Eq for Author {}
-
-impl Hash for Author {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.name.hash(state);
-    }
-}
-
 const DELIMITER: &str = "|||";
 
 const GIT_LOG_PARSE_FIELDS: [&str; 6] = ["%an", "%ae", "%aD", "%s", "%b", "%H"];
-const DEFAULT_GIT_LOG_SINCE: &str = "3 months ago";
-const MIN_LARGE_GIT_REPO_NUM_COMMITS: usize = 1000;
+pub const DEFAULT_GIT_LOG_SINCE_DAYS: i64 = 90;
+pub const MIN_LARGE_GIT_REPO_NUM_COMMITS: usize = 1000;
+
+// (since, until), either of which can be open
+pub type DateRange = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
 
 #[derive(Debug, Clone)]
 pub struct FilterConfig {
-    pub author: Option<Author>,
-    pub date_range: Option<(Option<DateTime<Utc>>, Option<DateTime<Utc>>)>,
+    // a result must be by one of these people, if there are any
+    pub authors: Vec<Identity>,
+    pub excluded_authors: Vec<Identity>,
+    pub date_range: Option<DateRange>,
+    // patterns for `paths::path_matches`, of which a commit has to touch at least one
+    pub paths: Vec<String>,
+    // a commit that only touches paths matching these patterns is left out
+    pub excluded_paths: Vec<String>,
+    // a result whose text contains all words of one of these keywords is left out, e.g.
+    // "dependency bump"
+    pub excluded_keywords: Vec<String>,
+    // phrases and boolean combinations of terms that the text of a result must match
+    pub text_filter: Option<TextQuery>,
     pub git_log_get_all: bool,
 }
 
+impl FilterConfig {
+    pub fn matches(&self, author: &Author, date: &DateTime<Utc>) -> bool {
+        self.matches_author(author) && self.matches_date(date)
+    }
+
+    pub fn matches_author(&self, author: &Author) -> bool {
+        (self.authors.is_empty() || self.authors.iter().any(|identity| identity.matches(author)))
+            && !self
+                .excluded_authors
+                .iter()
+                .any(|identity| identity.matches(author))
+    }
+
+    // Whether the texts match the text filter and none of the excluded keywords occur in them,
+    // compared case-insensitively and ignoring plurals, e.g. "dependency bumps" also matches "Bump
+    // the dependency".
+    pub fn matches_text(&self, texts: &[&str]) -> bool {
+        if self.excluded_keywords.is_empty() && self.text_filter.is_none() {
+            return true;
+        }
+        let text = normalize_text(&texts.join("\n"));
+        if let Some(text_filter) = &self.text_filter {
+            if !text_filter.matches(&text) {
+                return false;
+            }
+        }
+        !self.excluded_keywords.iter().any(|keyword| {
+            let words: Vec<String> = keyword
+                .split_whitespace()
+                .map(|word| strip_plural(&word.to_lowercase()).to_string())
+                .collect();
+            !words.is_empty() && words.iter().all(|word| text.contains(word.as_str()))
+        })
+    }
+
+    // Keeps the commits that touch the paths of the filter, following renames: once a commit
+    // renamed a matching file, the older commits of the file under its old name match too.
+    // `commits` must be ordered from oldest to newest.
+    pub fn filter_paths(&self, commits: Vec<Commit>) -> Vec<Commit> {
+        if self.paths.is_empty() && self.excluded_paths.is_empty() {
+            return commits;
+        }
+        let mut followed_paths: HashSet<String> = HashSet::new();
+        let mut filtered: Vec<Commit> = Vec::with_capacity(commits.len());
+        for commit in commits.into_iter().rev() {
+            let is_match = |path: &String| {
+                self.paths.is_empty()
+                    || followed_paths.contains(path)
+                    || self.paths.iter().any(|pattern| path_matches(pattern, path))
+            };
+            let is_excluded = |path: &String| {
+                self.excluded_paths
+                    .iter()
+                    .any(|pattern| path_matches(pattern, path))
+            };
+            if !commit
+                .changed_paths
+                .iter()
+                .any(|path| is_match(path) && !is_excluded(path))
+            {
+                continue;
+            }
+            let old_paths: Vec<String> = commit
+                .renames
+                .iter()
+                .filter(|(_, new_path)| is_match(new_path))
+                .map(|(old_path, _)| old_path.clone())
+                .collect();
+            followed_paths.extend(old_paths);
+            filtered.push(commit);
+        }
+        filtered.reverse();
+        filtered
+    }
+
+    pub fn matches_date(&self, date: &DateTime<Utc>) -> bool {
+        if let Some((date_since, date_until)) = self.date_range {
+            if let Some(start_date) = date_since {
+                if date < &start_date {
+                    return false;
+                }
+            }
+            if let Some(end_date) = date_until {
+                if date > &end_date {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
 impl Client {
     pub fn new() -> Client {
-        Client
+        Client::default()
+    }
+
+    pub fn dir(mut self, dir: PathBuf) -> Client {
+        self.dir = Some(dir);
+        self
+    }
+
+    fn git(&self) -> Command {
+        let mut command = Command::new("git");
+        if let Some(dir) = &self.dir {
+            command.current_dir(dir);
+        }
+        command
     }
 
-    pub fn get_number_of_commits(&self) -> Result<usize, Box<dyn std::error::Error>> {
-        let output = Command::new("git")
+    pub fn get_number_of_commits(&self) -> Result<usize, GitmError> {
+        let output = self
+            .git()
             .arg("rev-list")
             .arg("--count")
             .arg("HEAD")
-            .output()?;
-        if !output.status.success() {
-            return Err("Failed to get git rev-list".into());
-        }
+            .output()
+            .map_err(git_command_error)?;
+        check_git_status(&output, "Failed to get git rev-list")?;
         let stdout = String::from_utf8(output.stdout)?;
-        let count = stdout.trim().parse::<usize>()?;
+        let count = stdout
+            .trim()
+            .parse::<usize>()
+            .map_err(|e| GitmError::Parse(format!("Invalid commit count {:?}: {}", stdout, e)))?;
         Ok(count)
     }
 
-    pub fn get_all_commits(
-        &self,
-        config: Option<FilterConfig>,
-    ) -> Result<Vec<Commit>, Box<dyn std::error::Error>> {
-        let format = format!("--pretty=format:{}", GIT_LOG_PARSE_FIELDS.join(DELIMITER));
-        let mut binding = Command::new("git");
-        let cmd = binding.arg("log").arg("-uz").arg(format);
-        if let Some(config) = &config {
-            if !config.git_log_get_all {
-                let num_commits = self.get_number_of_commits()?;
-                if num_commits > MIN_LARGE_GIT_REPO_NUM_COMMITS {
-                    cmd.arg(format!("--since=\"{}\"", DEFAULT_GIT_LOG_SINCE));
-                }
-            }
+    pub fn get_log_entries(&self, shas: &[String]) -> Result<Vec<LogEntry>, GitmError> {
+        if shas.is_empty() {
+            return Ok(Vec::new());
         }
-        let output = cmd.output()?;
-        if !output.status.success() {
-            return Err("Failed to get git log".into());
+        let format = format!("--pretty=format:{}", GIT_LOG_PARSE_FIELDS.join(DELIMITER));
+        let mut child = self
+            .git()
+            .arg("log")
+            .arg("-uz")
+            .arg("--no-walk=unsorted")
+            .arg("--stdin")
+            .arg(format)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(git_command_error)?;
+        // feed the shas through stdin as the list can be too long for argv
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(shas.join("\n").as_bytes())
+                .map_err(git_command_error)?;
         }
+        let output = child.wait_with_output().map_err(git_command_error)?;
+        check_git_status(&output, "Failed to get git log")?;
         let stdout = String::from_utf8(output.stdout)?;
-        let commit_data_split = stdout.split("\0").collect::<Vec<&str>>();
-        let mut commits = Vec::with_capacity(commit_data_split.len());
-        for commit_data in commit_data_split {
-            let (udiff_strings, remaining_log) = split_raw_git_log_output(commit_data);
-            if udiff_strings.is_empty() || remaining_log.is_empty() {
-                continue;
-            }
-            let mut patch_set = PatchSet::new();
-            patch_set.parse(udiff_strings.join("\n")).unwrap();
-            let mut parts = remaining_log
-                .split(DELIMITER)
-                .collect::<Vec<&str>>()
-                .into_iter();
-            if parts.len() != GIT_LOG_PARSE_FIELDS.len() {
-                continue;
-            }
-            let author_name = parts.next().unwrap().to_string();
-            let author_email: Option<String> = match parts.next().unwrap() {
-                "" => None,
-                email => Some(email.to_string()),
-            };
-            let date_raw = parts.next().unwrap().trim().to_string();
-            let title = parts.next().unwrap().trim().to_string();
-            let body = parts.next().unwrap().trim().to_string();
-            let sha = parts.next().unwrap().trim().to_string();
-            let date = DateTime::parse_from_rfc2822(&date_raw)?.with_timezone(&Utc);
-            let author = Author {
-                name: Some(author_name),
-                username: None,
-                email: author_email,
-            };
-            if let Some(config) = &config {
-                if let Some(filter_author) = &config.author {
-                    if filter_author != &author {
-                        continue;
-                    }
-                }
-                if let Some((date_since, date_after)) = config.date_range {
-                    if let Some(start_date) = date_since {
-                        if date < start_date {
-                            continue;
-                        }
-                    }
-                    if let Some(end_date) = date_after {
-                        if date > end_date {
-                            continue;
-                        }
-                    }
-                }
-            }
-            commits.push(Commit {
-                author,
-                date,
-                title,
-                body,
-                sha,
-                patch_set,
-                display_mode: CommitDisplayMode::TitleAndBody,
-            });
-        }
-        commits.reverse();
-        Ok(commits)
+        parse_git_log_output(&stdout)
+    }
+
+    pub fn get_patch_set(&self, sha: &str) -> Result<PatchSet, GitmError> {
+        let output = self
+            .git()
+            .arg("show")
+            .arg("-u")
+            .arg("--format=")
+            .arg(sha)
+            .output()
+            .map_err(git_command_error)?;
+        check_git_status(&output, "Failed to get git show")?;
+        parse_patch_set(sha, String::from_utf8(output.stdout)?)
+    }
+
+    pub fn get_head_sha(&self) -> Result<String, GitmError> {
+        let output = self
+            .git()
+            .arg("rev-parse")
+            .arg("HEAD")
+            .output()
+            .map_err(git_command_error)?;
+        check_git_status(&output, "Failed to get git HEAD")?;
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    }
+
+    pub fn get_git_dir(&self) -> Result<PathBuf, GitmError> {
+        let output = self
+            .git()
+            .arg("rev-parse")
+            .arg("--git-dir")
+            .output()
+            .map_err(git_command_error)?;
+        check_git_status(&output, "Failed to get git dir")?;
+        let git_dir = PathBuf::from(String::from_utf8(output.stdout)?.trim());
+        // relative to the directory that git ran in
+        Ok(match &self.dir {
+            Some(dir) => dir.join(git_dir),
+            None => git_dir,
+        })
     }
 
-    pub fn get_all_authors(&self) -> Result<Vec<Author>, Box<dyn std::error::Error>> {
-        let output = Command::new("git")
+    pub fn get_work_tree_dir(&self) -> Result<PathBuf, GitmError> {
+        let output = self
+            .git()
+            .arg("rev-parse")
+            .arg("--show-toplevel")
+            .output()
+            .map_err(git_command_error)?;
+        check_git_status(&output, "Failed to get git work tree")?;
+        Ok(PathBuf::from(String::from_utf8(output.stdout)?.trim()))
+    }
+
+    pub fn get_remote_url(&self, remote: &str) -> Result<String, GitmError> {
+        let output = self
+            .git()
+            .arg("remote")
+            .arg("get-url")
+            .arg(remote)
+            .output()
+            .map_err(git_command_error)?;
+        check_git_status(
+            &output,
+            &format!("Failed to get the url of remote {}", remote),
+        )?;
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    }
+
+    pub fn get_reachable_shas(&self) -> Result<Vec<String>, GitmError> {
+        let output = self
+            .git()
+            .arg("rev-list")
+            .arg("HEAD")
+            .output()
+            .map_err(git_command_error)?;
+        check_git_status(&output, "Failed to get git rev-list")?;
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(stdout.lines().map(|line| line.trim().to_string()).collect())
+    }
+
+    pub fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool, GitmError> {
+        // exits with 1 if not an ancestor and 128 if either commit no longer exists
+        let output = self
+            .git()
+            .arg("merge-base")
+            .arg("--is-ancestor")
+            .arg(ancestor)
+            .arg(descendant)
+            .output()
+            .map_err(git_command_error)?;
+        Ok(output.status.success())
+    }
+
+    // The paths of the files at HEAD, relative to the root of the repository.
+    pub fn get_all_files(&self) -> Result<Vec<String>, GitmError> {
+        let output = self
+            .git()
+            .arg("ls-files")
+            .arg("--full-name")
+            .arg(":/")
+            .output()
+            .map_err(git_command_error)?;
+        check_git_status(&output, "Failed to get git ls-files")?;
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(stdout.lines().map(|line| line.to_string()).collect())
+    }
+
+    pub fn get_all_authors(&self) -> Result<Vec<Author>, GitmError> {
+        let output = self
+            .git()
             .arg("log")
             .arg(format!("--pretty=format:%an{}%ae", DELIMITER))
-            .output()?;
-        if !output.status.success() {
-            return Err("Failed to get git shortlog".into());
-        }
+            .output()
+            .map_err(git_command_error)?;
+        check_git_status(&output, "Failed to get git shortlog")?;
         let stdout = String::from_utf8(output.stdout)?;
         let mut authors = Vec::new();
         for line in stdout.lines() {
@@ -258,6 +441,209 @@ impl Client {
         }
         Ok(authors)
     }
+
+    pub fn get_all_tags(&self) -> Result<Vec<Tag>, GitmError> {
+        // the date of the tagged commit, so that lightweight and annotated tags behave the same
+        let output = self.git()
+            .arg("for-each-ref")
+            .arg("refs/tags")
+            .arg(format!(
+                "--format=%(refname:short){}%(*committerdate:iso-strict){}%(committerdate:iso-strict)",
+                DELIMITER, DELIMITER
+            ))
+            .output()
+            .map_err(git_command_error)?;
+        check_git_status(&output, "Failed to get git tags")?;
+        let stdout = String::from_utf8(output.stdout)?;
+        let mut tags = Vec::new();
+        for line in stdout.lines() {
+            let parts: Vec<&str> = line.split(DELIMITER).collect();
+            if parts.len() != 3 {
+                continue;
+            }
+            // only one of the dates is set, depending on whether the tag is annotated
+            let date = match DateTime::parse_from_rfc3339(parts[1])
+                .or_else(|_| DateTime::parse_from_rfc3339(parts[2]))
+            {
+                Ok(date) => date.with_timezone(&Utc),
+                // e.g. tags of trees or blobs
+                Err(_) => continue,
+            };
+            tags.push(Tag {
+                name: parts[0].to_string(),
+                date,
+            });
+        }
+        Ok(tags)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub name: String,
+    pub date: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub author: Author,
+    pub date: DateTime<Utc>,
+    pub title: String,
+    pub body: String,
+    pub sha: String,
+    pub udiff: String,
+}
+
+impl LogEntry {
+    pub fn into_commit(self) -> Result<Commit, GitmError> {
+        let renames = parse_renames(&self.udiff);
+        let patch_set = parse_patch_set(&self.sha, self.udiff)?;
+        Ok(Commit {
+            author: self.author,
+            date: self.date,
+            title: self.title,
+            body: self.body,
+            sha: self.sha,
+            added_code: added_code(&patch_set),
+            changed_paths: changed_paths(&patch_set, &renames),
+            renames,
+            display_mode: CommitDisplayMode::TitleAndBody,
+        })
+    }
+}
+
+fn parse_patch_set(sha: &str, udiff: String) -> Result<PatchSet, GitmError> {
+    let mut patch_set = PatchSet::new();
+    patch_set.parse(udiff).map_err(|e| {
+        GitmError::Parse(format!(
+            "Failed to parse the patch of commit {}: {}",
+            sha, e
+        ))
+    })?;
+    Ok(patch_set)
+}
+
+fn added_code(patch_set: &PatchSet) -> String {
+    let mut added_line_content = Vec::<String>::new();
+    for file in patch_set.files() {
+        for hunk in file.hunks() {
+            for line in hunk.lines() {
+                if line.is_added() {
+                    added_line_content.push(line.value.clone());
+                }
+            }
+        }
+    }
+    added_line_content.join("\n")
+}
+
+fn removed_code(patch_set: &PatchSet) -> String {
+    let mut removed_line_content = Vec::<String>::new();
+    for file in patch_set.files() {
+        for hunk in file.hunks() {
+            for line in hunk.lines() {
+                if line.is_removed() {
+                    removed_line_content.push(line.value.clone());
+                }
+            }
+        }
+    }
+    removed_line_content.join("\n")
+}
+
+fn changed_paths(patch_set: &PatchSet, renames: &[(String, String)]) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for file in patch_set.files() {
+        for (path, prefix) in [(&file.source_file, "a/"), (&file.target_file, "b/")] {
+            if path == "/dev/null" {
+                continue;
+            }
+            let path = path.strip_prefix(prefix).unwrap_or(path).to_string();
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    // pure renames have no hunks and are not part of the patch set
+    for (old_path, new_path) in renames.iter() {
+        for path in [old_path, new_path] {
+            if !paths.contains(path) {
+                paths.push(path.clone());
+            }
+        }
+    }
+    paths
+}
+
+fn git_command_error(error: std::io::Error) -> GitmError {
+    GitmError::Git(format!("Failed to run git: {}", error))
+}
+
+// Fails with the message of git (e.g. "not a git repository") if the command did not succeed.
+fn check_git_status(output: &Output, action: &str) -> Result<(), GitmError> {
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(GitmError::Git(match stderr.trim() {
+        "" => action.to_string(),
+        stderr => format!("{}: {}", action, stderr),
+    }))
+}
+
+fn parse_git_log_output(stdout: &str) -> Result<Vec<LogEntry>, GitmError> {
+    let commit_data_split = stdout.split("\0").collect::<Vec<&str>>();
+    let mut entries = Vec::with_capacity(commit_data_split.len());
+    for commit_data in commit_data_split {
+        let (udiff_strings, remaining_log) = split_raw_git_log_output(commit_data);
+        if udiff_strings.is_empty() || remaining_log.is_empty() {
+            continue;
+        }
+        let mut parts = remaining_log
+            .split(DELIMITER)
+            .collect::<Vec<&str>>()
+            .into_iter();
+        if parts.len() != GIT_LOG_PARSE_FIELDS.len() {
+            continue;
+        }
+        let author_name = parts.next().unwrap().to_string();
+        let author_email: Option<String> = match parts.next().unwrap() {
+            "" => None,
+            email => Some(email.to_string()),
+        };
+        let date_raw = parts.next().unwrap().trim().to_string();
+        let title = parts.next().unwrap().trim().to_string();
+        let body = parts.next().unwrap().trim().to_string();
+        let sha = parts.next().unwrap().trim().to_string();
+        let date = DateTime::parse_from_rfc2822(&date_raw)?.with_timezone(&Utc);
+        entries.push(LogEntry {
+            author: Author {
+                name: Some(author_name),
+                username: None,
+                email: author_email,
+            },
+            date,
+            title,
+            body,
+            sha,
+            udiff: udiff_strings.join("\n"),
+        });
+    }
+    Ok(entries)
+}
+
+// e.g. "rename from src/lexer.rs" followed by "rename to src/tokenizer.rs"
+fn parse_renames(udiff: &str) -> Vec<(String, String)> {
+    let mut renames = Vec::new();
+    let mut old_path: Option<&str> = None;
+    for line in udiff.lines() {
+        if let Some(path) = line.strip_prefix("rename from ") {
+            old_path = Some(path);
+        } else if let (Some(path), Some(old)) = (line.strip_prefix("rename to "), old_path.take()) {
+            renames.push((old.to_string(), path.to_string()));
+        }
+    }
+    renames
 }
 
 fn split_raw_git_log_output(s: &str) -> (Vec<String>, String) {
@@ -286,3 +672,88 @@ fn split_raw_git_log_output(s: &str) -> (Vec<String>, String) {
     }
     (udiff_strings, remaining_log)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn filter_config(paths: &[&str], excluded_paths: &[&str]) -> FilterConfig {
+        FilterConfig {
+            authors: Vec::new(),
+            excluded_authors: Vec::new(),
+            date_range: None,
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+            excluded_paths: excluded_paths.iter().map(|path| path.to_string()).collect(),
+            excluded_keywords: Vec::new(),
+            text_filter: None,
+            git_log_get_all: false,
+        }
+    }
+
+    fn commit(sha: &str, changed_paths: &[&str], renames: &[(&str, &str)]) -> Commit {
+        Commit {
+            author: Author {
+                name: Some(String::from("Jane Doe")),
+                username: None,
+                email: Some(String::from("jane@example.com")),
+            },
+            date: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            title: format!("commit {}", sha),
+            body: String::new(),
+            sha: sha.to_string(),
+            added_code: String::new(),
+            changed_paths: changed_paths.iter().map(|path| path.to_string()).collect(),
+            renames: renames
+                .iter()
+                .map(|(old_path, new_path)| (old_path.to_string(), new_path.to_string()))
+                .collect(),
+            display_mode: CommitDisplayMode::Title,
+        }
+    }
+
+    fn shas(commits: Vec<Commit>) -> Vec<String> {
+        commits.into_iter().map(|commit| commit.sha).collect()
+    }
+
+    // oldest first
+    fn history() -> Vec<Commit> {
+        vec![
+            commit("a", &["src/lexer.rs"], &[]),
+            commit("b", &["src/main.rs"], &[]),
+            commit(
+                "c",
+                &["src/lexer.rs", "src/tokenizer.rs"],
+                &[("src/lexer.rs", "src/tokenizer.rs")],
+            ),
+            commit("d", &["src/tokenizer.rs", "README.md"], &[]),
+            // an unrelated file that later reuses the old name
+            commit("e", &["src/lexer.rs"], &[]),
+        ]
+    }
+
+    #[test]
+    fn test_filter_paths() {
+        let commits = filter_config(&["src/main.rs"], &[]).filter_paths(history());
+        assert_eq!(shas(commits), vec!["b"]);
+        let commits = filter_config(&["src"], &[]).filter_paths(history());
+        assert_eq!(shas(commits), vec!["a", "b", "c", "d", "e"]);
+        // a commit is left out if it only touches excluded paths
+        let commits = filter_config(&[], &["src/lexer.rs", "*.md"]).filter_paths(history());
+        assert_eq!(shas(commits), vec!["b", "c", "d"]);
+        let commits = filter_config(&[], &[]).filter_paths(history());
+        assert_eq!(commits.len(), 5);
+    }
+
+    #[test]
+    fn test_filter_paths_follows_renames() {
+        let commits = filter_config(&["src/tokenizer.rs"], &[]).filter_paths(history());
+        // "a" changed the file under its old name, "e" is a new file with the old name
+        assert_eq!(shas(commits), vec!["a", "c", "d"]);
+        let commits = filter_config(&["tokenizer.rs"], &[]).filter_paths(history());
+        assert_eq!(shas(commits), vec!["a", "c", "d"]);
+        // the old name alone does not follow the rename forward
+        let commits = filter_config(&["src/lexer.rs"], &[]).filter_paths(history());
+        assert_eq!(shas(commits), vec!["a", "c", "e"]);
+    }
+}
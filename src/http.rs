@@ -0,0 +1,467 @@
+use crate::error::GitmError;
+use crate::llm::ChatError;
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+// reasoning models can take a while to answer a prompt with 50 rerank candidates
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 120;
+pub const DEFAULT_MAX_ATTEMPTS: usize = 4;
+const DEFAULT_INITIAL_BACKOFF_MILLIS: u64 = 500;
+const DEFAULT_MAX_BACKOFF_SECS: u64 = 30;
+// a Retry-After beyond this fails the request instead of stalling the search
+const MAX_RETRY_AFTER_SECS: u64 = 120;
+
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    pub connect_timeout: Duration,
+    // the timeout of a single attempt, including reading the response
+    pub request_timeout: Duration,
+    // 1 disables retries
+    pub max_attempts: usize,
+    // doubled after every failed attempt up to `max_backoff`, unless the server sets Retry-After
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+            request_timeout: Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: Duration::from_millis(DEFAULT_INITIAL_BACKOFF_MILLIS),
+            max_backoff: Duration::from_secs(DEFAULT_MAX_BACKOFF_SECS),
+        }
+    }
+}
+
+impl RetryConfig {
+    fn backoff(&self, num_failed_attempts: usize) -> Duration {
+        let factor = 2u32.saturating_pow(num_failed_attempts.saturating_sub(1) as u32);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+// Cancels the requests of every client that shares it, e.g. on Ctrl-C. Requests that are in
+// flight are aborted and no new attempts are made.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    // Resolves once the token is cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+// A connection pool that is shared by all clones, so that every request to a provider reuses the
+// same connections, with retries of the requests that are worth retrying.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    retry_config: RetryConfig,
+    cancellation: CancellationToken,
+}
+
+impl HttpClient {
+    // Fails if the TLS backend cannot be initialized.
+    pub fn new(retry_config: RetryConfig) -> Result<HttpClient, GitmError> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(retry_config.connect_timeout)
+            .timeout(retry_config.request_timeout)
+            .build()
+            .map_err(|e| GitmError::Config(format!("Failed to create the HTTP client: {}", e)))?;
+        Ok(HttpClient {
+            client,
+            retry_config,
+            cancellation: CancellationToken::new(),
+        })
+    }
+
+    pub fn cancellation(mut self, cancellation: CancellationToken) -> HttpClient {
+        self.cancellation = cancellation;
+        self
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post(&self, url: String) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    // Runs the future unless it is cancelled first, e.g. a request that the caller retries itself.
+    pub async fn cancellable<F>(&self, future: F) -> Option<F::Output>
+    where
+        F: Future,
+    {
+        tokio::select! {
+            output = future => Some(output),
+            _ = self.cancellation.cancelled() => None,
+        }
+    }
+
+    // Sends the request and parses the JSON response. Connection errors, timeouts, 429 and 5xx
+    // responses are retried with exponential backoff, or after the time that the server asks for
+    // with Retry-After.
+    pub async fn send_json<T>(&self, request: RequestBuilder) -> Result<T, ChatError>
+    where
+        T: DeserializeOwned,
+    {
+        let mut request = Some(request);
+        let mut num_failed_attempts = 0;
+        while let Some(current) = request.take() {
+            if self.cancellation.is_cancelled() {
+                break;
+            }
+            // a request with a streaming body cannot be cloned and is only sent once
+            let attempt = match current.try_clone() {
+                Some(attempt) => {
+                    request = Some(current);
+                    attempt
+                }
+                None => current,
+            };
+            let failure = tokio::select! {
+                result = send_attempt::<T>(attempt) => match result {
+                    Ok(response) => return Ok(response),
+                    Err(failure) => failure,
+                },
+                _ = self.cancellation.cancelled() => break,
+            };
+            num_failed_attempts += 1;
+            if !failure.is_retryable
+                || request.is_none()
+                || num_failed_attempts >= self.retry_config.max_attempts
+            {
+                return Err(failure.error);
+            }
+            let wait = match failure.retry_after {
+                Some(retry_after) if retry_after > Duration::from_secs(MAX_RETRY_AFTER_SECS) => {
+                    return Err(failure.error)
+                }
+                Some(retry_after) => retry_after,
+                None => self.retry_config.backoff(num_failed_attempts),
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.cancellation.cancelled() => break,
+            }
+        }
+        Err(ChatError {
+            message: String::from("The request was cancelled"),
+        })
+    }
+}
+
+struct Failure {
+    error: ChatError,
+    is_retryable: bool,
+    retry_after: Option<Duration>,
+}
+
+async fn send_attempt<T>(request: RequestBuilder) -> Result<T, Failure>
+where
+    T: DeserializeOwned,
+{
+    let response = request.send().await.map_err(|e| Failure {
+        is_retryable: e.is_timeout() || e.is_connect() || e.is_request(),
+        error: ChatError::from(e),
+        retry_after: None,
+    })?;
+    let status = response.status();
+    if !status.is_success() {
+        let retry_after = retry_after(response.headers());
+        return Err(Failure {
+            error: ChatError {
+                message: format!("{}: {}", status, response.text().await.unwrap_or_default()),
+            },
+            is_retryable: status == StatusCode::TOO_MANY_REQUESTS
+                || status == StatusCode::REQUEST_TIMEOUT
+                || status.is_server_error(),
+            retry_after,
+        });
+    }
+    // a body that is cut off by the timeout is worth another attempt, a malformed one is not
+    let body = response.bytes().await.map_err(|e| Failure {
+        is_retryable: e.is_timeout(),
+        error: ChatError::from(e),
+        retry_after: None,
+    })?;
+    serde_json::from_slice::<T>(&body).map_err(|e| Failure {
+        error: ChatError::from(e),
+        is_retryable: false,
+        retry_after: None,
+    })
+}
+
+// The Retry-After header as either a number of seconds or an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get("retry-after")?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value)
+        .ok()?
+        .with_timezone(&Utc);
+    Some((date - Utc::now()).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockResponse, MockServer};
+    use reqwest::header::HeaderValue;
+    use serde_json::Value;
+    use std::time::Instant;
+
+    fn retry_config() -> RetryConfig {
+        RetryConfig {
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(5),
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(100),
+        }
+    }
+
+    async fn send(http: &HttpClient, server: &MockServer) -> Result<Value, ChatError> {
+        http.send_json(http.post(format!("{}/v1/chat", server.url())).body("{}"))
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_success() {
+        let server = MockServer::start(vec![MockResponse::new(200, r#"{"ok":true}"#)]).await;
+        let http = HttpClient::new(retry_config()).unwrap();
+        assert_eq!(send(&http, &server).await.unwrap()["ok"], true);
+        assert_eq!(server.requests(), vec!["POST /v1/chat HTTP/1.1"]);
+    }
+
+    #[tokio::test]
+    async fn test_retry_after() {
+        let server = MockServer::start(vec![
+            MockResponse::new(429, "slow down").header("Retry-After", "0"),
+            MockResponse::new(200, r#"{"ok":true}"#),
+        ])
+        .await;
+        // the backoff would outlast the test, so the retry has to follow Retry-After
+        let http = HttpClient::new(RetryConfig {
+            initial_backoff: Duration::from_secs(600),
+            max_backoff: Duration::from_secs(600),
+            ..retry_config()
+        })
+        .unwrap();
+        let start = Instant::now();
+        assert_eq!(send(&http, &server).await.unwrap()["ok"], true);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(server.num_requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_too_long() {
+        let server = MockServer::start(vec![
+            MockResponse::new(429, "slow down").header("Retry-After", "3600"),
+            MockResponse::new(200, r#"{"ok":true}"#),
+        ])
+        .await;
+        let http = HttpClient::new(retry_config()).unwrap();
+        let error = send(&http, &server).await.unwrap_err();
+        assert_eq!(error.message, "429 Too Many Requests: slow down");
+        assert_eq!(server.num_requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_server_error_then_success() {
+        let server = MockServer::start(vec![
+            MockResponse::new(503, "unavailable"),
+            MockResponse::new(200, r#"{"ok":true}"#),
+        ])
+        .await;
+        let http = HttpClient::new(retry_config()).unwrap();
+        assert_eq!(send(&http, &server).await.unwrap()["ok"], true);
+        assert_eq!(server.num_requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_server_error_until_max_attempts() {
+        let server = MockServer::start(vec![
+            MockResponse::new(500, "first"),
+            MockResponse::new(502, "second"),
+            MockResponse::new(503, "third"),
+            MockResponse::new(200, r#"{"ok":true}"#),
+        ])
+        .await;
+        let http = HttpClient::new(retry_config()).unwrap();
+        let error = send(&http, &server).await.unwrap_err();
+        assert_eq!(error.message, "503 Service Unavailable: third");
+        assert_eq!(server.num_requests(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_client_error_is_not_retried() {
+        let server = MockServer::start(vec![
+            MockResponse::new(400, "bad request"),
+            MockResponse::new(200, r#"{"ok":true}"#),
+        ])
+        .await;
+        let http = HttpClient::new(retry_config()).unwrap();
+        let error = send(&http, &server).await.unwrap_err();
+        assert_eq!(error.message, "400 Bad Request: bad request");
+        assert_eq!(server.num_requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_response_is_not_retried() {
+        let server = MockServer::start(vec![
+            MockResponse::new(200, "not json"),
+            MockResponse::new(200, r#"{"ok":true}"#),
+        ])
+        .await;
+        let http = HttpClient::new(retry_config()).unwrap();
+        assert!(send(&http, &server).await.is_err());
+        assert_eq!(server.num_requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let server = MockServer::start(vec![
+            MockResponse::new(200, r#"{"ok":true}"#).delay(Duration::from_secs(5)),
+            MockResponse::new(200, r#"{"ok":true}"#).delay(Duration::from_secs(5)),
+        ])
+        .await;
+        let http = HttpClient::new(RetryConfig {
+            request_timeout: Duration::from_millis(200),
+            max_attempts: 2,
+            ..retry_config()
+        })
+        .unwrap();
+        let start = Instant::now();
+        let error = send(&http, &server).await.unwrap_err();
+        assert!(error.message.contains("timed out"), "{}", error.message);
+        assert!(start.elapsed() < Duration::from_secs(4));
+        assert_eq!(server.num_requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_then_success() {
+        let server = MockServer::start(vec![
+            MockResponse::new(200, r#"{"ok":true}"#).delay(Duration::from_secs(5)),
+            MockResponse::new(200, r#"{"ok":true}"#),
+        ])
+        .await;
+        let http = HttpClient::new(RetryConfig {
+            request_timeout: Duration::from_millis(200),
+            ..retry_config()
+        })
+        .unwrap();
+        assert_eq!(send(&http, &server).await.unwrap()["ok"], true);
+        assert_eq!(server.num_requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_during_backoff() {
+        let server = MockServer::start(vec![
+            MockResponse::new(503, "unavailable"),
+            MockResponse::new(200, r#"{"ok":true}"#),
+        ])
+        .await;
+        let cancellation = CancellationToken::new();
+        let http = HttpClient::new(RetryConfig {
+            initial_backoff: Duration::from_secs(600),
+            max_backoff: Duration::from_secs(600),
+            ..retry_config()
+        })
+        .unwrap()
+        .cancellation(cancellation.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            cancellation.cancel();
+        });
+        let start = Instant::now();
+        let error = send(&http, &server).await.unwrap_err();
+        assert_eq!(error.message, "The request was cancelled");
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(server.num_requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_before_sending() {
+        let server = MockServer::start(vec![MockResponse::new(200, r#"{"ok":true}"#)]).await;
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+        let http = HttpClient::new(retry_config())
+            .unwrap()
+            .cancellation(cancellation);
+        let error = send(&http, &server).await.unwrap_err();
+        assert_eq!(error.message, "The request was cancelled");
+        assert_eq!(server.num_requests(), 0);
+    }
+
+    #[test]
+    fn test_retry_after_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert("retry-after", HeaderValue::from_static("30"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(30)));
+        // a date in the past means right away
+        headers.insert(
+            "retry-after",
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+        let date = (Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        headers.insert("retry-after", HeaderValue::from_str(&date).unwrap());
+        let wait = retry_after(&headers).unwrap();
+        assert!(wait > Duration::from_secs(55) && wait <= Duration::from_secs(60));
+        headers.insert("retry-after", HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let retry_config = RetryConfig {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(3),
+            ..RetryConfig::default()
+        };
+        assert_eq!(retry_config.backoff(1), Duration::from_millis(500));
+        assert_eq!(retry_config.backoff(2), Duration::from_secs(1));
+        assert_eq!(retry_config.backoff(3), Duration::from_secs(2));
+        assert_eq!(retry_config.backoff(4), Duration::from_secs(3));
+        assert_eq!(retry_config.backoff(100), Duration::from_secs(3));
+    }
+}
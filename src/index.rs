@@ -0,0 +1,516 @@
+use crate::document::{DocumentStats, Fielded};
+use crate::error::GitmError;
+use crate::git::{
+    self, Author, Commit, CommitDisplayMode, FilterConfig, LogEntry, DEFAULT_GIT_LOG_SINCE_DAYS,
+    MIN_LARGE_GIT_REPO_NUM_COMMITS,
+};
+use crate::splitters::Splitter;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+const INDEX_DIR: &str = "gitm";
+const COMMIT_INDEX_FILE: &str = "commits.json";
+// bump when the on-disk format or the term statistics change so that stale indexes are rebuilt
+const COMMIT_INDEX_VERSION: u32 = 4;
+
+// Directory under the git dir in which gitm persists its indexes and caches.
+pub fn index_dir(git_client: &git::Client) -> Result<PathBuf, GitmError> {
+    Ok(git_client.get_git_dir()?.join(INDEX_DIR))
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TermStats {
+    pub doc_len: usize,
+    pub term_freqs: HashMap<String, usize>,
+}
+
+impl TermStats {
+    pub fn from_text(text: &str, splitter: &dyn Splitter) -> TermStats {
+        let terms = splitter.split(text);
+        let mut term_freqs = HashMap::new();
+        for term in terms.iter() {
+            *term_freqs.entry(term.clone()).or_insert(0) += 1;
+        }
+        TermStats {
+            doc_len: terms.len(),
+            term_freqs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedCommit {
+    pub sha: String,
+    pub author_name: Option<String>,
+    pub author_email: Option<String>,
+    pub date: DateTime<Utc>,
+    pub title: String,
+    pub body: String,
+    // what ranking and path filtering need from the patch, extracted once when the commit is
+    // ingested so that patches are not kept in the index and re-parsed on every run
+    pub added_code: String,
+    pub changed_paths: Vec<String>,
+    pub renames: Vec<(String, String)>,
+    pub stats: DocumentStats,
+}
+
+impl IndexedCommit {
+    fn from_log_entry(entry: LogEntry) -> IndexedCommit {
+        let mut indexed_commit = IndexedCommit {
+            sha: entry.sha.clone(),
+            author_name: entry.author.name.clone(),
+            author_email: entry.author.email.clone(),
+            date: entry.date,
+            title: entry.title.clone(),
+            body: entry.body.clone(),
+            added_code: String::new(),
+            changed_paths: Vec::new(),
+            renames: Vec::new(),
+            stats: DocumentStats::default(),
+        };
+        // a patch that cannot be parsed still leaves the message searchable
+        if let Ok(commit) = entry.into_commit() {
+            indexed_commit.added_code = commit.added_code;
+            indexed_commit.changed_paths = commit.changed_paths;
+            indexed_commit.renames = commit.renames;
+        }
+        indexed_commit.stats = indexed_commit.to_commit().document().stats();
+        indexed_commit
+    }
+
+    pub fn author(&self) -> Author {
+        Author {
+            name: self.author_name.clone(),
+            username: None,
+            email: self.author_email.clone(),
+        }
+    }
+
+    pub fn to_commit(&self) -> Commit {
+        Commit {
+            author: self.author(),
+            date: self.date,
+            title: self.title.clone(),
+            body: self.body.clone(),
+            sha: self.sha.clone(),
+            added_code: self.added_code.clone(),
+            changed_paths: self.changed_paths.clone(),
+            renames: self.renames.clone(),
+            display_mode: CommitDisplayMode::TitleAndBody,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CommitIndexFile {
+    version: u32,
+    head: Option<String>,
+    commits: Vec<IndexedCommit>,
+    // commits without a patch (e.g. merges) are not searchable but are remembered so that they
+    // are not re-ingested on every run
+    skipped: Vec<String>,
+}
+
+pub struct CommitIndex {
+    path: PathBuf,
+    head: Option<String>,
+    commits: HashMap<String, IndexedCommit>,
+    skipped: HashSet<String>,
+}
+
+impl CommitIndex {
+    pub fn open(git_client: &git::Client) -> Result<CommitIndex, GitmError> {
+        let path = index_dir(git_client)?.join(COMMIT_INDEX_FILE);
+        let mut index = CommitIndex {
+            path,
+            head: None,
+            commits: HashMap::new(),
+            skipped: HashSet::new(),
+        };
+        if !index.path.exists() {
+            return Ok(index);
+        }
+        let raw = fs::read_to_string(&index.path)?;
+        // an unreadable or outdated index is not an error, it is just rebuilt from scratch
+        let file = match serde_json::from_str::<CommitIndexFile>(&raw) {
+            Ok(file) if file.version == COMMIT_INDEX_VERSION => file,
+            _ => return Ok(index),
+        };
+        index.head = file.head;
+        index.skipped = file.skipped.into_iter().collect();
+        index.commits = file
+            .commits
+            .into_iter()
+            .map(|commit| (commit.sha.clone(), commit))
+            .collect();
+        Ok(index)
+    }
+
+    pub fn clear(&mut self) {
+        self.head = None;
+        self.commits.clear();
+        self.skipped.clear();
+    }
+
+    // Brings the index in sync with HEAD. Returns true if the index changed.
+    pub fn update(&mut self, git_client: &git::Client) -> Result<bool, GitmError> {
+        let head = git_client.get_head_sha()?;
+        if self.head.as_deref() == Some(head.as_str()) {
+            return Ok(false);
+        }
+        if let Some(old_head) = &self.head {
+            // the previously indexed HEAD is gone or diverged, so history was rewritten
+            if !git_client.is_ancestor(old_head, &head)? {
+                self.clear();
+            }
+        }
+        let reachable: HashSet<String> = git_client.get_reachable_shas()?.into_iter().collect();
+        self.commits.retain(|sha, _| reachable.contains(sha));
+        self.skipped.retain(|sha| reachable.contains(sha));
+        let missing: Vec<String> = reachable
+            .into_iter()
+            .filter(|sha| !self.commits.contains_key(sha) && !self.skipped.contains(sha))
+            .collect();
+        let mut ingested = HashSet::with_capacity(missing.len());
+        for entry in git_client.get_log_entries(&missing)? {
+            ingested.insert(entry.sha.clone());
+            self.commits
+                .insert(entry.sha.clone(), IndexedCommit::from_log_entry(entry));
+        }
+        for sha in missing {
+            if !ingested.contains(&sha) {
+                self.skipped.insert(sha);
+            }
+        }
+        self.head = Some(head);
+        Ok(true)
+    }
+
+    pub fn save(&self) -> Result<(), GitmError> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut commits: Vec<IndexedCommit> = self.commits.values().cloned().collect();
+        commits.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.sha.cmp(&b.sha)));
+        let mut skipped: Vec<String> = self.skipped.iter().cloned().collect();
+        skipped.sort();
+        let file = CommitIndexFile {
+            version: COMMIT_INDEX_VERSION,
+            head: self.head.clone(),
+            commits,
+            skipped,
+        };
+        // write to a temporary file first so that an interrupted run never leaves a corrupt index
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string(&file)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.commits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commits.is_empty()
+    }
+
+    pub fn get(&self, sha: &str) -> Option<&IndexedCommit> {
+        self.commits.get(sha)
+    }
+
+    pub fn stats(&self, sha: &str) -> Option<&DocumentStats> {
+        self.commits.get(sha).map(|commit| &commit.stats)
+    }
+
+    // Returns the indexed commits that pass the filter, oldest first. No patch is parsed, the code
+    // and paths of the commits were extracted when they were ingested.
+    pub fn commits(&self, config: Option<&FilterConfig>) -> Result<Vec<Commit>, GitmError> {
+        let mut since: Option<DateTime<Utc>> = None;
+        if let Some(config) = config {
+            // an explicit date range replaces the default window, e.g. "in 2023"
+            if !config.git_log_get_all
+                && config.date_range.is_none()
+                && self.commits.len() > MIN_LARGE_GIT_REPO_NUM_COMMITS
+            {
+                since = Some(Utc::now() - Duration::days(DEFAULT_GIT_LOG_SINCE_DAYS));
+            }
+        }
+        let mut indexed_commits: Vec<&IndexedCommit> = Vec::new();
+        for commit in self.commits.values() {
+            if let Some(since) = since {
+                if commit.date < since {
+                    continue;
+                }
+            }
+            if let Some(config) = config {
+                if !config.matches(&commit.author(), &commit.date)
+                    || !config.matches_text(&[&commit.title, &commit.body])
+                {
+                    continue;
+                }
+            }
+            indexed_commits.push(commit);
+        }
+        indexed_commits.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.sha.cmp(&b.sha)));
+        let mut commits: Vec<Commit> = indexed_commits
+            .into_iter()
+            .map(IndexedCommit::to_commit)
+            .collect();
+        if let Some(config) = config {
+            commits = config.filter_paths(commits);
+        }
+        Ok(commits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::process::Command;
+
+    // A git repository in a temporary directory, removed when dropped.
+    struct TestRepo {
+        dir: PathBuf,
+        num_commits: std::cell::Cell<i64>,
+    }
+
+    impl TestRepo {
+        fn new(name: &str) -> TestRepo {
+            let dir = std::env::temp_dir().join(format!(
+                "gitm-test-index-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let repo = TestRepo {
+                dir,
+                num_commits: std::cell::Cell::new(0),
+            };
+            repo.git(&["init", "-q", "-b", "main"]);
+            repo.git(&["config", "user.name", "Jane Doe"]);
+            repo.git(&["config", "user.email", "jane@example.com"]);
+            repo.git(&["config", "commit.gpgsign", "false"]);
+            repo
+        }
+
+        fn git(&self, args: &[&str]) -> String {
+            // one commit per minute, so that the commits are ordered by date
+            let date = format!("{} +0000", 1_700_000_000 + 60 * self.num_commits.get());
+            let output = Command::new("git")
+                .args(args)
+                .current_dir(&self.dir)
+                .env("GIT_AUTHOR_DATE", &date)
+                .env("GIT_COMMITTER_DATE", &date)
+                .output()
+                .unwrap();
+            assert!(
+                output.status.success(),
+                "git {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            String::from_utf8(output.stdout).unwrap().trim().to_string()
+        }
+
+        // Writes the file and commits it, returning the sha of the commit.
+        fn commit(&self, path: &str, content: &str, message: &str) -> String {
+            let file = self.dir.join(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, content).unwrap();
+            self.git(&["add", path]);
+            self.num_commits.set(self.num_commits.get() + 1);
+            self.git(&["commit", "-q", "-m", message]);
+            self.head()
+        }
+
+        fn head(&self) -> String {
+            self.git(&["rev-parse", "HEAD"])
+        }
+
+        fn client(&self) -> git::Client {
+            git::Client::new().dir(self.dir.clone())
+        }
+
+        fn index_path(&self) -> PathBuf {
+            self.dir
+                .join(".git")
+                .join(INDEX_DIR)
+                .join(COMMIT_INDEX_FILE)
+        }
+    }
+
+    impl Drop for TestRepo {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn shas(index: &CommitIndex) -> HashSet<String> {
+        index.commits.keys().cloned().collect()
+    }
+
+    fn open_and_update(client: &git::Client) -> (CommitIndex, bool) {
+        let mut index = CommitIndex::open(client).unwrap();
+        let changed = index.update(client).unwrap();
+        (index, changed)
+    }
+
+    #[test]
+    fn test_builds_index() {
+        let repo = TestRepo::new("build");
+        let first = repo.commit("src/main.rs", "fn main() {}\n", "Add main");
+        let second = repo.commit("README.md", "# gitm\n", "Add readme");
+        let client = repo.client();
+        let (index, changed) = open_and_update(&client);
+        assert!(changed);
+        assert_eq!(shas(&index), HashSet::from([first.clone(), second.clone()]));
+        assert_eq!(index.head.as_deref(), Some(second.as_str()));
+        assert_eq!(index.get(&first).unwrap().title, "Add main");
+        let commits = index.commits(None).unwrap();
+        assert_eq!(
+            commits
+                .iter()
+                .map(|commit| commit.sha.as_str())
+                .collect::<Vec<_>>(),
+            vec![first.as_str(), second.as_str()]
+        );
+    }
+
+    #[test]
+    fn test_appends_new_commits() {
+        let repo = TestRepo::new("append");
+        let first = repo.commit("a.txt", "a\n", "First");
+        let client = repo.client();
+        let (index, _) = open_and_update(&client);
+        index.save().unwrap();
+        // an unchanged HEAD needs no update
+        let (_, changed) = open_and_update(&client);
+        assert!(!changed);
+
+        let second = repo.commit("b.txt", "b\n", "Second");
+        let mut index = CommitIndex::open(&client).unwrap();
+        assert_eq!(shas(&index), HashSet::from([first.clone()]));
+        // the indexed commit is kept as is rather than ingested again
+        index.commits.get_mut(&first).unwrap().title = String::from("kept");
+        assert!(index.update(&client).unwrap());
+        assert_eq!(shas(&index), HashSet::from([first.clone(), second.clone()]));
+        assert_eq!(index.get(&first).unwrap().title, "kept");
+        assert_eq!(index.head.as_deref(), Some(second.as_str()));
+    }
+
+    #[test]
+    fn test_rebuilds_after_rewrite() {
+        let repo = TestRepo::new("rewrite");
+        let first = repo.commit("a.txt", "a\n", "First");
+        let second = repo.commit("b.txt", "b\n", "Second");
+        let client = repo.client();
+        let (mut index, _) = open_and_update(&client);
+        index.commits.get_mut(&first).unwrap().title = String::from("stale");
+        index.save().unwrap();
+
+        repo.git(&["commit", "-q", "--amend", "-m", "Second, amended"]);
+        let amended = repo.head();
+        assert!(!client.is_ancestor(&second, &amended).unwrap());
+        let (index, changed) = open_and_update(&client);
+        assert!(changed);
+        assert_eq!(
+            shas(&index),
+            HashSet::from([first.clone(), amended.clone()])
+        );
+        assert_eq!(index.get(&amended).unwrap().title, "Second, amended");
+        // the whole index was rebuilt, not only the rewritten commits
+        assert_eq!(index.get(&first).unwrap().title, "First");
+    }
+
+    #[test]
+    fn test_drops_unreachable_commits() {
+        let repo = TestRepo::new("reset");
+        let first = repo.commit("a.txt", "a\n", "First");
+        let second = repo.commit("b.txt", "b\n", "Second");
+        let client = repo.client();
+        let (index, _) = open_and_update(&client);
+        index.save().unwrap();
+        repo.git(&["reset", "-q", "--hard", first.as_str()]);
+        let (index, changed) = open_and_update(&client);
+        assert!(changed);
+        assert!(!shas(&index).contains(&second));
+        assert_eq!(index.head.as_deref(), Some(first.as_str()));
+    }
+
+    #[test]
+    fn test_remembers_skipped_merges() {
+        let repo = TestRepo::new("merge");
+        repo.commit("a.txt", "a\n", "First");
+        repo.git(&["checkout", "-q", "-b", "feature"]);
+        let feature = repo.commit("b.txt", "b\n", "Feature");
+        repo.git(&["checkout", "-q", "main"]);
+        let main = repo.commit("c.txt", "c\n", "Main");
+        repo.git(&["merge", "-q", "--no-ff", "--no-edit", "feature"]);
+        let merge = repo.head();
+        let client = repo.client();
+        let (index, _) = open_and_update(&client);
+        assert!(shas(&index).contains(&feature));
+        assert!(shas(&index).contains(&main));
+        // a merge has no patch of its own
+        assert!(!shas(&index).contains(&merge));
+        assert!(index.skipped.contains(&merge));
+        index.save().unwrap();
+
+        let reopened = CommitIndex::open(&client).unwrap();
+        assert!(reopened.skipped.contains(&merge));
+        let next = repo.commit("d.txt", "d\n", "Next");
+        let mut index = reopened;
+        assert!(index.update(&client).unwrap());
+        assert!(shas(&index).contains(&next));
+        assert!(index.skipped.contains(&merge));
+    }
+
+    #[test]
+    fn test_discards_other_versions() {
+        let repo = TestRepo::new("version");
+        repo.commit("a.txt", "a\n", "First");
+        let client = repo.client();
+        let (index, _) = open_and_update(&client);
+        index.save().unwrap();
+        let raw = fs::read_to_string(repo.index_path()).unwrap();
+        let mut file: serde_json::Value = serde_json::from_str(&raw).unwrap();
+        assert_eq!(file["version"], COMMIT_INDEX_VERSION);
+        assert!(file["commits"][0].get("udiff").is_none());
+
+        file["version"] = serde_json::json!(COMMIT_INDEX_VERSION - 1);
+        fs::write(repo.index_path(), file.to_string()).unwrap();
+        let index = CommitIndex::open(&client).unwrap();
+        assert!(index.is_empty());
+        assert_eq!(index.head, None);
+
+        // an unreadable index is rebuilt as well
+        fs::write(repo.index_path(), "{\"version\": 4, \"commits\": [").unwrap();
+        let (index, changed) = open_and_update(&client);
+        assert!(changed);
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn test_save_replaces_index_atomically() {
+        let repo = TestRepo::new("save");
+        repo.commit("a.txt", "a\n", "First");
+        let client = repo.client();
+        let (index, _) = open_and_update(&client);
+        // a temporary file left by an interrupted run is overwritten
+        let tmp_path = repo.index_path().with_extension("json.tmp");
+        fs::create_dir_all(tmp_path.parent().unwrap()).unwrap();
+        fs::write(&tmp_path, "garbage").unwrap();
+        index.save().unwrap();
+        assert!(!tmp_path.exists());
+        assert!(Path::new(&repo.index_path()).exists());
+        let reopened = CommitIndex::open(&client).unwrap();
+        assert_eq!(shas(&reopened), shas(&index));
+        assert_eq!(reopened.head, index.head);
+    }
+}
@@ -1,14 +1,31 @@
+pub mod analysis;
+pub mod authors;
 pub mod bm25;
 pub mod classifier;
+pub mod dates;
+pub mod document;
+pub mod embeddings;
+pub mod error;
 pub mod fmt;
+pub mod fusion;
 pub mod git;
 pub mod github;
+pub mod http;
+pub mod index;
 pub mod llm;
 pub mod mention_classifiers;
+#[cfg(test)]
+mod mock_server;
+pub mod paths;
 pub mod prompts;
+pub mod providers;
+pub mod query_parser;
+pub mod query_syntax;
 pub mod rankers;
+pub mod reranker;
 pub mod retrievers;
 pub mod search_agent;
+pub mod search_result;
 pub mod splitters;
 pub mod store;
 pub mod utils;
@@ -1,59 +1,128 @@
+use crate::http::HttpClient;
+use crate::providers::{AnthropicProvider, AzureOpenAIProvider, OllamaProvider, OpenAIProvider};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
 
+pub trait ChatProvider {
+    fn chat(
+        &self,
+        messages: Vec<Message>,
+        tools: Option<Vec<Tool>>,
+        temperature: f64,
+    ) -> impl Future<Output = Result<ChatResponse, ChatError>> + Send;
+}
+
+// The provider that is picked at runtime (e.g. from the command line).
 #[derive(Debug, Clone)]
-pub struct ChatModel {
-    pub api_key: String,
-    pub model_key: ChatModelKey,
+pub enum ChatModel {
+    OpenAI(OpenAIProvider),
+    AzureOpenAI(AzureOpenAIProvider),
+    Anthropic(AnthropicProvider),
+    Ollama(OllamaProvider),
 }
 
 impl ChatModel {
-    pub fn new(api_key: String, model_key: ChatModelKey) -> Self {
-        Self { api_key, model_key }
+    pub fn new(api_key: String, model_key: ChatModelKey, http: HttpClient) -> Self {
+        ChatModel::OpenAI(OpenAIProvider::new(api_key, model_key.to_string(), http))
     }
 
-    pub async fn chat(
+    pub async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, ChatError> {
+        match self {
+            ChatModel::OpenAI(provider) => provider.embed(inputs).await,
+            ChatModel::Ollama(provider) => provider.embed(inputs).await,
+            ChatModel::AzureOpenAI(_) | ChatModel::Anthropic(_) => Err(ChatError {
+                message: format!("The {} provider does not support embeddings", self.name()),
+            }),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            ChatModel::OpenAI(provider) => provider.name(),
+            ChatModel::AzureOpenAI(_) => String::from("azure"),
+            ChatModel::Anthropic(_) => String::from("anthropic"),
+            ChatModel::Ollama(provider) => provider.name(),
+        }
+    }
+}
+
+impl ChatProvider for ChatModel {
+    async fn chat(
         &self,
         messages: Vec<Message>,
         tools: Option<Vec<Tool>>,
         temperature: f64,
     ) -> Result<ChatResponse, ChatError> {
-        let url = format!("{}{}", OPENAI_API_BASE, OPENAI_API_CHAT_ENDPOINT);
-        let client = reqwest::Client::new();
-        let body = ChatRequestBody {
-            model: self.model_key.clone(),
-            messages,
-            temperature: temperature,
-            tools,
-        };
-        let response = client
-            .post(url)
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&body)
-            .send()
-            .await;
-        match response {
-            Ok(response) => {
-                if response.status() != reqwest::StatusCode::OK {
-                    return Err(ChatError {
-                        message: format!(
-                            "{}: {}",
-                            response.status(),
-                            response.text().await.unwrap()
-                        ),
-                    });
-                }
-                let chat_response = response.json::<ChatResponse>().await.unwrap();
-                return Ok(chat_response);
-            }
-            Err(e) => {
-                return Err(ChatError {
-                    message: format!("{}", e),
-                });
-            }
-        };
+        match self {
+            ChatModel::OpenAI(provider) => provider.chat(messages, tools, temperature).await,
+            ChatModel::AzureOpenAI(provider) => provider.chat(messages, tools, temperature).await,
+            ChatModel::Anthropic(provider) => provider.chat(messages, tools, temperature).await,
+            ChatModel::Ollama(provider) => provider.chat(messages, tools, temperature).await,
+        }
+    }
+}
+
+impl From<OpenAIProvider> for ChatModel {
+    fn from(provider: OpenAIProvider) -> Self {
+        ChatModel::OpenAI(provider)
+    }
+}
+
+impl From<AzureOpenAIProvider> for ChatModel {
+    fn from(provider: AzureOpenAIProvider) -> Self {
+        ChatModel::AzureOpenAI(provider)
+    }
+}
+
+impl From<AnthropicProvider> for ChatModel {
+    fn from(provider: AnthropicProvider) -> Self {
+        ChatModel::Anthropic(provider)
+    }
+}
+
+impl From<OllamaProvider> for ChatModel {
+    fn from(provider: OllamaProvider) -> Self {
+        ChatModel::Ollama(provider)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProviderKind {
+    OpenAI,
+    AzureOpenAI,
+    Anthropic,
+    Ollama,
+}
+
+impl ProviderKind {
+    // the environment variable that holds the API key, if the provider needs one
+    pub fn api_key_env_var(&self) -> Option<&'static str> {
+        match self {
+            ProviderKind::OpenAI => Some("OPENAI_API_KEY"),
+            ProviderKind::AzureOpenAI => Some("AZURE_OPENAI_API_KEY"),
+            ProviderKind::Anthropic => Some("ANTHROPIC_API_KEY"),
+            ProviderKind::Ollama => None,
+        }
+    }
+}
+
+impl FromStr for ProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "openai" => Ok(ProviderKind::OpenAI),
+            "azure" => Ok(ProviderKind::AzureOpenAI),
+            "anthropic" => Ok(ProviderKind::Anthropic),
+            "ollama" => Ok(ProviderKind::Ollama),
+            _ => Err(format!(
+                "unknown provider: {} (expected \"openai\", \"azure\", \"anthropic\" or \"ollama\")",
+                s
+            )),
+        }
     }
 }
 
@@ -103,7 +172,7 @@ impl<'de> Deserialize<'de> for ChatModelKey {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChatRequestBody {
-    pub model: ChatModelKey,
+    pub model: String,
     pub messages: Vec<Message>,
     pub temperature: f64,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -213,6 +282,23 @@ pub struct Property {
     pub description: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingRequestBody {
+    pub model: String,
+    pub input: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingResponse {
+    pub data: Vec<Embedding>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Embedding {
+    pub index: usize,
+    pub embedding: Vec<f32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatResponse {
     pub choices: Vec<Choice>,
@@ -231,9 +317,6 @@ pub struct Usage {
     pub total_tokens: u32,
 }
 
-const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
-const OPENAI_API_CHAT_ENDPOINT: &str = "/chat/completions";
-
 #[derive(Debug)]
 pub struct ChatError {
     pub message: String,
@@ -245,6 +328,16 @@ impl fmt::Display for ChatError {
     }
 }
 
+impl std::error::Error for ChatError {}
+
+impl From<reqwest::Error> for ChatError {
+    fn from(error: reqwest::Error) -> Self {
+        ChatError {
+            message: format!("{}", error),
+        }
+    }
+}
+
 impl From<serde_json::Error> for ChatError {
     fn from(error: serde_json::Error) -> Self {
         ChatError {
@@ -1,9 +1,28 @@
 use clap::Parser;
-use gitm::llm::ChatModel;
+use gitm::bm25::{
+    FieldBoosts, DEFAULT_BODY_BOOST, DEFAULT_CODE_BOOST, DEFAULT_PATHS_BOOST, DEFAULT_TITLE_BOOST,
+};
+use gitm::error::GitmError;
+use gitm::fusion::{FusionConfig, FusionStrategy};
+use gitm::github;
+use gitm::github::{IssueFilterConfig, IssueState};
+use gitm::http::{
+    CancellationToken, HttpClient, RetryConfig, DEFAULT_CONNECT_TIMEOUT_SECS, DEFAULT_MAX_ATTEMPTS,
+    DEFAULT_REQUEST_TIMEOUT_SECS,
+};
 use gitm::llm::ChatModelKey::Gpt4;
-use gitm::search_agent::SearchConfigBuilder;
+use gitm::llm::{ChatModel, ProviderKind};
+use gitm::providers::{
+    AnthropicProvider, AzureOpenAIProvider, OllamaProvider, OpenAIProvider,
+    DEFAULT_ANTHROPIC_MODEL, DEFAULT_OLLAMA_MODEL,
+};
+use gitm::reranker::DEFAULT_RERANK_TOP_N;
+use gitm::search_agent::{SearchAgent, SearchConfigBuilder};
+use gitm::search_result::fmt_ranked_result;
 use gitm::utils::{does_command_exist, does_valid_git_dir_exist};
 use std::env;
+use std::process::ExitCode;
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[clap(
@@ -13,9 +32,69 @@ use std::env;
 struct Args {
     query: String,
 
-    #[arg(long, default_value = "", help = "OpenAI API key")]
+    #[arg(
+        long,
+        default_value = "",
+        help = "API key of the LLM provider; defaults to OPENAI_API_KEY, AZURE_OPENAI_API_KEY or ANTHROPIC_API_KEY depending on --provider"
+    )]
     api_key: String,
 
+    #[arg(
+        long,
+        default_value = "openai",
+        help = "The LLM provider: \"openai\" (or any OpenAI-compatible endpoint), \"azure\", \"anthropic\" or \"ollama\""
+    )]
+    provider: ProviderKind,
+
+    #[arg(
+        long,
+        default_value = "",
+        help = "The model to use (the deployment name for Azure OpenAI); defaults to a model of the provider"
+    )]
+    model: String,
+
+    #[arg(
+        long,
+        default_value = "",
+        help = "Base URL of the LLM provider, e.g. http://localhost:8000/v1 for an OpenAI-compatible server or the Azure OpenAI endpoint"
+    )]
+    llm_base_url: String,
+
+    #[arg(
+        long,
+        default_value_t = DEFAULT_REQUEST_TIMEOUT_SECS,
+        help = "Seconds after which a request to the LLM provider is abandoned and retried"
+    )]
+    llm_timeout: u64,
+
+    #[arg(
+        long,
+        default_value_t = DEFAULT_CONNECT_TIMEOUT_SECS,
+        help = "Seconds to wait for a connection to the LLM provider"
+    )]
+    llm_connect_timeout: u64,
+
+    #[arg(
+        long,
+        default_value_t = DEFAULT_MAX_ATTEMPTS,
+        help = "How many times a request to the LLM provider is attempted when it times out or fails with a 429 or 5xx status"
+    )]
+    llm_max_attempts: usize,
+
+    #[arg(
+        long,
+        default_value = "",
+        help = "GitHub token used to fetch issues and pull requests; defaults to GITHUB_TOKEN. Without a token, gh is used if it is installed"
+    )]
+    github_token: String,
+
+    #[arg(
+        long,
+        default_value = "",
+        help = "GitHub API base URL, e.g. https://github.example.com/api/v3 for GitHub Enterprise; defaults to GITHUB_API_URL or is derived from the origin remote"
+    )]
+    github_api_url: String,
+
     #[arg(
         long,
         default_value = "false",
@@ -30,6 +109,50 @@ struct Args {
     )]
     issues_too: bool,
 
+    #[arg(
+        long,
+        default_value = "false",
+        help = "If set, only GitHub pull requests will be searched"
+    )]
+    prs_only: bool,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "If set, GitHub pull requests will be searched"
+    )]
+    prs_too: bool,
+
+    #[arg(
+        long = "path",
+        help = "Only search commits that touch this path, directory or glob (e.g. \"src/**/*.rs\"); can be repeated"
+    )]
+    paths: Vec<String>,
+
+    #[arg(
+        long = "exclude-path",
+        help = "Leave out commits that only touch this path, directory or glob (e.g. \"docs/\"); can be repeated"
+    )]
+    excluded_paths: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Only search issues and pull requests in this state: \"open\" or \"closed\" (which includes merged pull requests)"
+    )]
+    state: Option<IssueState>,
+
+    #[arg(
+        long = "label",
+        help = "Only search issues and pull requests with this label; can be repeated"
+    )]
+    labels: Vec<String>,
+
+    #[arg(
+        long = "exclude-label",
+        help = "Leave out issues and pull requests with this label; can be repeated"
+    )]
+    excluded_labels: Vec<String>,
+
     #[arg(
         long,
         default_value = "false",
@@ -37,6 +160,91 @@ struct Args {
     )]
     include_code_patches: bool,
 
+    #[arg(
+        long,
+        default_value = "false",
+        help = "If set, embeddings will be used to also find results with a similar meaning"
+    )]
+    semantic: bool,
+
+    #[arg(
+        long,
+        default_value = "",
+        help = "The embedding model of the provider for --semantic; defaults to text-embedding-3-small for OpenAI and nomic-embed-text for Ollama"
+    )]
+    embedding_model: String,
+
+    #[arg(
+        long,
+        default_value = "rrf",
+        help = "How the results of the keyword and semantic passes are merged: \"rrf\" (reciprocal rank fusion) or \"weighted\" (weighted normalized scores)"
+    )]
+    fusion: FusionStrategy,
+
+    #[arg(
+        long,
+        alias = "message-weight",
+        default_value = "1.0",
+        help = "Weight of the keyword pass over titles, bodies, code and paths"
+    )]
+    keyword_weight: f64,
+
+    #[arg(
+        long,
+        default_value_t = DEFAULT_TITLE_BOOST,
+        help = "How much a match in the title of a commit, issue or pull request counts"
+    )]
+    title_boost: f64,
+
+    #[arg(
+        long,
+        default_value_t = DEFAULT_BODY_BOOST,
+        help = "How much a match in the body of a commit message, issue or pull request counts"
+    )]
+    body_boost: f64,
+
+    #[arg(
+        long,
+        default_value_t = DEFAULT_CODE_BOOST,
+        help = "How much a match in the code that a commit adds counts, with --include-code-patches"
+    )]
+    code_boost: f64,
+
+    #[arg(
+        long,
+        default_value_t = DEFAULT_PATHS_BOOST,
+        help = "How much a match in the paths of the files that a commit changed counts"
+    )]
+    path_boost: f64,
+
+    #[arg(
+        long,
+        default_value = "1.0",
+        help = "Weight of the semantic (embedding) pass"
+    )]
+    semantic_weight: f64,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "If set, the top candidates are reordered by the LLM according to their relevance to the query"
+    )]
+    rerank: bool,
+
+    #[arg(
+        long,
+        default_value_t = DEFAULT_RERANK_TOP_N,
+        help = "The number of top candidates that are sent to the LLM when --rerank is set"
+    )]
+    rerank_top_n: usize,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "If set, no LLM is used: author and date filters are extracted from the query with rules and no API key is needed"
+    )]
+    offline: bool,
+
     #[arg(
         long,
         default_value = "false",
@@ -50,62 +258,216 @@ struct Args {
         help = "If set, the search will be performed on all (issues or commits); by default, the last 2 months of data are searched."
     )]
     all: bool,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "If set, the on-disk commit index in .git/gitm is discarded and rebuilt"
+    )]
+    rebuild_index: bool,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "If set, how long each stage of the search took is printed to stderr"
+    )]
+    timings: bool,
 }
 
-fn get_args() -> Result<Args, Box<dyn std::error::Error>> {
+fn get_args() -> Result<Args, GitmError> {
     let args = Args::parse();
-    if args.api_key == "" {
-        match env::var("OPENAI_API_KEY") {
-            Ok(api_key) => {
-                return Ok(Args { api_key, ..args });
-            }
-            Err(_) => {
-                if args.api_key == "" {
-                    return Err("No API key provided. Set OPENAI_API_KEY as an env var or pass it with the --api-key flag".into());
+    let config_error = |message: &str| Err(GitmError::Config(String::from(message)));
+    if args.issues_only && args.issues_too {
+        return config_error("Cannot specify both --issues-only and --issues-too");
+    } else if args.prs_only && args.prs_too {
+        return config_error("Cannot specify both --prs-only and --prs-too");
+    } else if args.offline && args.rerank {
+        return config_error("Cannot specify both --offline and --rerank, reranking needs an LLM");
+    }
+    if args.api_key.is_empty() && !args.offline {
+        if let Some(env_var) = args.provider.api_key_env_var() {
+            match env::var(env_var) {
+                Ok(api_key) => {
+                    return Ok(Args { api_key, ..args });
+                }
+                Err(_) => {
+                    return Err(GitmError::Config(format!("No API key provided. Set {} as an env var, pass it with the --api-key flag or search without an LLM with --offline", env_var)));
                 }
             }
         }
-    } else if args.issues_only && args.issues_too {
-        return Err("Cannot specify both --issues-only and --issues-too".into());
     }
     Ok(args)
 }
 
+fn get_model(args: &Args, cancellation: CancellationToken) -> Result<ChatModel, GitmError> {
+    let api_key = args.api_key.clone();
+    let http = HttpClient::new(RetryConfig {
+        connect_timeout: Duration::from_secs(args.llm_connect_timeout),
+        request_timeout: Duration::from_secs(args.llm_timeout),
+        max_attempts: args.llm_max_attempts.max(1),
+        ..RetryConfig::default()
+    })?
+    .cancellation(cancellation);
+    let model = match args.provider {
+        ProviderKind::OpenAI => {
+            let model_name = if args.model.is_empty() {
+                Gpt4.to_string()
+            } else {
+                args.model.clone()
+            };
+            let mut provider = OpenAIProvider::new(api_key, model_name, http);
+            if !args.llm_base_url.is_empty() {
+                provider = provider.base_url(args.llm_base_url.clone());
+            }
+            if !args.embedding_model.is_empty() {
+                provider = provider.embedding_model(args.embedding_model.clone());
+            }
+            ChatModel::from(provider)
+        }
+        ProviderKind::AzureOpenAI => {
+            let endpoint = if args.llm_base_url.is_empty() {
+                env::var("AZURE_OPENAI_ENDPOINT").map_err(|_| {
+                    GitmError::Config(String::from("No Azure OpenAI endpoint provided. Set AZURE_OPENAI_ENDPOINT as an env var or pass it with the --llm-base-url flag"))
+                })?
+            } else {
+                args.llm_base_url.clone()
+            };
+            if args.model.is_empty() {
+                return Err(GitmError::Config(String::from(
+                    "No Azure OpenAI deployment provided. Pass it with the --model flag",
+                )));
+            }
+            ChatModel::from(AzureOpenAIProvider::new(
+                api_key,
+                endpoint,
+                args.model.clone(),
+                http,
+            ))
+        }
+        ProviderKind::Anthropic => {
+            let model_name = if args.model.is_empty() {
+                String::from(DEFAULT_ANTHROPIC_MODEL)
+            } else {
+                args.model.clone()
+            };
+            let mut provider = AnthropicProvider::new(api_key, model_name, http);
+            if !args.llm_base_url.is_empty() {
+                provider = provider.base_url(args.llm_base_url.clone());
+            }
+            ChatModel::from(provider)
+        }
+        ProviderKind::Ollama => {
+            let model_name = if args.model.is_empty() {
+                String::from(DEFAULT_OLLAMA_MODEL)
+            } else {
+                args.model.clone()
+            };
+            let mut provider = OllamaProvider::new(model_name, http);
+            if !args.llm_base_url.is_empty() {
+                provider = provider.base_url(args.llm_base_url.clone());
+            }
+            if !args.embedding_model.is_empty() {
+                provider = provider.embedding_model(args.embedding_model.clone());
+            }
+            ChatModel::from(provider)
+        }
+    };
+    Ok(model)
+}
+
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = get_args();
-    if let Err(e) = args {
-        println!("\n{}", e);
-        return Ok(());
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("\n{}", e);
+            if let Some(hint) = e.hint() {
+                eprintln!("{}", hint);
+            }
+            ExitCode::from(e.exit_code())
+        }
     }
-    let args = args.unwrap();
+}
+
+async fn run() -> Result<(), GitmError> {
+    let args = get_args()?;
     if !does_command_exist("git")? {
-        println!("Git is not installed");
-        return Ok(());
-    } else if !does_command_exist("gh")? {
-        println!("Github CLI is not installed");
-        return Ok(());
+        return Err(GitmError::Git(String::from("Git is not installed")));
     } else if !does_valid_git_dir_exist()? {
-        println!("Not a valid git directory");
-        return Ok(());
+        return Err(GitmError::Git(String::from("Not a valid git directory")));
     }
 
-    let model = ChatModel::new(args.api_key, Gpt4);
-    let search_agent = gitm::search_agent::SearchAgent::new(model);
+    // Ctrl-C aborts the search along with the requests to the LLM provider and GitHub and their
+    // retries
+    let cancellation = CancellationToken::new();
+    let ctrl_c_cancellation = cancellation.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            ctrl_c_cancellation.cancel();
+        }
+    });
+    let search_agent = if args.offline {
+        SearchAgent::offline()?
+    } else {
+        SearchAgent::new(get_model(&args, cancellation.clone())?)?
+    };
+    let mut github_client = github::Client::new()?
+        .http_client(HttpClient::new(RetryConfig::default())?.cancellation(cancellation.clone()));
+    if !args.github_token.is_empty() {
+        github_client = github_client.token(args.github_token);
+    }
+    if !args.github_api_url.is_empty() {
+        github_client = github_client.api_url(args.github_api_url);
+    }
+    let search_agent = search_agent.github_client(github_client);
     let search_config = SearchConfigBuilder::new(args.query)
         .max_num_results(10)
-        .include_commits(!args.issues_only)
-        .include_issues(args.issues_too)
+        .include_commits(!args.issues_only && !args.prs_only)
+        .include_issues(args.issues_only || args.issues_too)
+        .include_pull_requests(args.prs_only || args.prs_too)
         .include_code_patches(args.include_code_patches)
+        .include_semantic(args.semantic)
+        .rerank(args.rerank)
+        .rerank_top_n(args.rerank_top_n)
         .disable_classifications(args.disable_classifications)
         .search_all(args.all)
+        .rebuild_index(args.rebuild_index)
+        .paths(args.paths)
+        .excluded_paths(args.excluded_paths)
+        .issue_filter(IssueFilterConfig {
+            state: args.state,
+            labels: args.labels,
+            excluded_labels: args.excluded_labels,
+            ..IssueFilterConfig::default()
+        })
+        .fusion(FusionConfig {
+            strategy: args.fusion,
+            keyword_weight: args.keyword_weight,
+            semantic_weight: args.semantic_weight,
+            ..FusionConfig::default()
+        })
+        .field_boosts(FieldBoosts {
+            title: args.title_boost,
+            body: args.body_boost,
+            code: args.code_boost,
+            paths: args.path_boost,
+        })
         .build();
-    let (commits, issues) = search_agent.search(search_config).await.unwrap();
-    for commit in commits {
-        println!("{}", commit.mock_git_log_fmt());
+    let response = tokio::select! {
+        response = search_agent.search(search_config) => response?,
+        _ = cancellation.cancelled() => return Err(GitmError::Cancelled),
+    };
+    // the search went on without the parts that failed
+    for warning in response.warnings.iter() {
+        eprintln!("warning: {}", warning);
     }
-    for issue in issues {
-        println!("{}", issue.mock_gh_issue_fmt());
+    for result in response.results {
+        println!("{}", fmt_ranked_result(&result));
+    }
+    if args.timings {
+        for (stage, duration) in response.timings.iter() {
+            eprintln!("{:>20}: {:>8.1} ms", stage, duration.as_secs_f64() * 1000.0);
+        }
     }
     Ok(())
 }
@@ -1,206 +1,246 @@
-use crate::classifier::{BinaryClassificationResult, BinaryClassifier, LLMBinaryClassifierContext};
-use crate::git::Author;
-use crate::llm::{ChatError, ChatModel, Property};
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-
-pub struct AuthorMentionBinaryClassifier {
-    existing_authors: HashSet<Author>,
-    raw_classifier: LLMBinaryClassifierContext,
+use crate::authors::AuthorResolver;
+use crate::classifier::{BinaryClassificationResult, BinaryClassifier};
+use crate::dates::{find_date_phrases, find_date_range};
+use crate::git::{DateRange, Tag};
+use crate::llm::ChatError;
+use crate::paths::{is_glob, resolve_path_mention};
+use crate::utils::split_query_words;
+use chrono::{DateTime, Local, TimeZone, Utc};
+use std::collections::HashMap;
+use std::ops::Range;
+
+// Words that introduce an author, e.g. "by alice" or "commits from bob".
+const AUTHOR_TRIGGER_WORDS: [&str; 4] = ["by", "from", "author", "author:"];
+// Words that follow a possessive author mention without an apostrophe, e.g. "johns commits".
+const AUTHORED_WORDS: [&str; 10] = [
+    "commit", "commits", "change", "changes", "work", "pr", "prs", "issue", "issues", "patches",
+];
+// Words that join several mentions, e.g. "by alice or bob".
+const CONJUNCTION_WORDS: [&str; 3] = ["or", "and", "nor"];
+// the longest author name (in words) that is looked up after a trigger word
+const MAX_AUTHOR_NAME_WORDS: usize = 3;
+
+// Words that negate the filter after them, e.g. "not by the bot" or "excluding docs/".
+pub const NEGATION_WORDS: [&str; 4] = ["not", "except", "excluding", "without"];
+
+// A filter value found in a query, along with the phrase that it was found in as a range of word
+// indices into `split_query_words(query)`, e.g. "not by john smith".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mention {
+    pub value: String,
+    pub phrase: Range<usize>,
+    pub negated: bool,
 }
 
-impl AuthorMentionBinaryClassifier {
-    pub fn new(model: ChatModel, existing_authors: HashSet<Author>) -> Self {
-        let instruction =
-            String::from("Determine if the user's query is trying to filter by the author.");
-        let result_property = (
-            String::from("author_name"),
-            Property {
-                r#type: String::from("string"),
-                description: String::from(
-                    "The name of the author that the user is trying to filter by (if classification == true)",
-                ),
-            },
-        );
-        let raw_classifier = LLMBinaryClassifierContext::builder(model, instruction)
-            .result_property(result_property)
-            .additional_information(format!("## Complete Author List\n{}\n\n*The author name must be an exact match to the author's name in the list above.*", 
-                existing_authors
-                    .iter()
-                    .filter(|author| author.name.is_some())
-                    .map(|author| format!("- {}", author.name.clone().unwrap()))
-                    .collect::<Vec<String>>()
-                    .join("\n")
-            ))
-            .build();
-        Self {
-            raw_classifier,
-            existing_authors,
-        }
-    }
+// Finds author mentions without an LLM by looking up the words after "by", "from", etc. with the
+// author resolver. Returns the mentions as they are written in the query.
+pub struct RuleBasedAuthorMentionClassifier {
+    resolver: AuthorResolver,
 }
 
-impl BinaryClassifier<Author> for AuthorMentionBinaryClassifier {
-    async fn classify(
-        &self,
-        query: String,
-    ) -> Result<BinaryClassificationResult<Author>, ChatError> {
-        let result = self.raw_classifier.raw_classification(query).await;
-        #[derive(Debug, Serialize, Deserialize)]
-        struct RawResult {
-            classification: bool,
-            author_name: Option<String>,
-        }
-        match result {
-            Ok(tool_call) => {
-                let result =
-                    serde_json::from_str::<RawResult>(tool_call.function.arguments.as_str());
-                match result {
-                    Ok(result) => {
-                        if result.classification {
-                            match result.author_name {
-                                Some(author_name) => {
-                                    let author = Author {
-                                        name: Some(author_name.clone()),
-                                        email: None,
-                                        username: None,
-                                    };
-                                    if self.existing_authors.contains(&author) {
-                                        Ok(BinaryClassificationResult {
-                                            classification: true,
-                                            content: Some(author),
-                                        })
-                                    } else {
-                                        Ok(BinaryClassificationResult {
-                                            classification: false,
-                                            content: None,
-                                        })
-                                    }
-                                }
-                                None => {
-                                    return Ok(BinaryClassificationResult {
-                                        classification: false,
-                                        content: None,
-                                    })
-                                }
-                            }
-                        } else {
-                            Ok(BinaryClassificationResult {
-                                classification: false,
-                                content: None,
-                            })
+impl RuleBasedAuthorMentionClassifier {
+    pub fn new(resolver: AuthorResolver) -> Self {
+        Self { resolver }
+    }
+
+    // The first author that the query asks for.
+    pub fn find_author(&self, query: &str) -> Option<String> {
+        self.find_author_mentions(query)
+            .into_iter()
+            .find(|mention| !mention.negated)
+            .map(|mention| mention.value)
+    }
+
+    // Finds all author mentions, e.g. "by alice or bob", "not by the bot" or "except johns
+    // commits".
+    pub fn find_author_mentions(&self, query: &str) -> Vec<Mention> {
+        let words = split_query_words(query);
+        let mut mentions: Vec<Mention> = Vec::new();
+        let mut i = 0;
+        while i < words.len() {
+            let is_negation = NEGATION_WORDS.contains(&words[i].as_str());
+            let is_preceded_by_negation = i > 0 && NEGATION_WORDS.contains(&words[i - 1].as_str());
+            if is_negation || AUTHOR_TRIGGER_WORDS.contains(&words[i].as_str()) {
+                let negated = is_negation || is_preceded_by_negation;
+                let mut start = if is_preceded_by_negation && !is_negation {
+                    i - 1
+                } else {
+                    i
+                };
+                let mut j = i + 1;
+                while let Some((mention, len)) = self.mention_at(&words, j) {
+                    mentions.push(Mention {
+                        value: mention,
+                        phrase: start..j + len,
+                        negated,
+                    });
+                    j += len;
+                    // "alice or bob"
+                    match words.get(j) {
+                        Some(word) if CONJUNCTION_WORDS.contains(&word.as_str()) => {
+                            start = j;
+                            j += 1;
                         }
+                        _ => break,
                     }
-                    Err(e) => Err(ChatError::from(e)),
                 }
+                if j > i + 1 {
+                    i = j;
+                    continue;
+                }
+            }
+            // possessives ("john's", "johns commits") are handled by the resolver
+            let is_possessive = words[i].ends_with("'s")
+                || (i + 1 < words.len() && AUTHORED_WORDS.contains(&words[i + 1].as_str()));
+            if is_possessive && self.resolver.resolve(words[i].as_str()).is_some() {
+                mentions.push(Mention {
+                    value: words[i].clone(),
+                    phrase: if is_preceded_by_negation { i - 1 } else { i }..i + 1,
+                    negated: is_preceded_by_negation,
+                });
             }
-            Err(e) => Err(e),
+            i += 1;
         }
+        mentions
+    }
+
+    // The longest author name at the start of `words[start..]`, with its length in words.
+    fn mention_at(&self, words: &[String], start: usize) -> Option<(String, usize)> {
+        (1..=MAX_AUTHOR_NAME_WORDS).rev().find_map(|len| {
+            let mention = words.get(start..start + len)?.join(" ");
+            self.resolver
+                .resolve(mention.as_str())
+                .map(|_| (mention, len))
+        })
+    }
+}
+
+impl BinaryClassifier<String> for RuleBasedAuthorMentionClassifier {
+    async fn classify(
+        &self,
+        query: String,
+    ) -> Result<BinaryClassificationResult<String>, ChatError> {
+        let author = self.find_author(query.as_str());
+        Ok(BinaryClassificationResult {
+            classification: author.is_some(),
+            content: author,
+        })
     }
 }
 
-pub struct DateTimeMentionClassifier {
-    raw_classifier: LLMBinaryClassifierContext,
+// Finds date mentions without an LLM, see `dates::find_date_range`.
+pub struct RuleBasedDateTimeMentionClassifier {
+    tags: HashMap<String, DateTime<Utc>>,
 }
 
-impl DateTimeMentionClassifier {
-    pub fn new(model: ChatModel) -> Self {
-        let instruction =
-            String::from("Determine if the user's query is trying to filter by the date.");
-        let before_date_property = (
-            String::from("since"),
-            Property {
-                r#type: String::from("string"),
-                description: String::from(
-                    "The YYYY-MM-DD since date that the user is trying to filter by",
-                ),
-            },
-        );
-        let after_date_property = (
-            String::from("until"),
-            Property {
-                r#type: String::from("string"),
-                description: String::from(
-                    "The YYYY-MM-DD until date that the user is trying to filter by",
-                ),
-            },
-        );
-        let current_datetime = chrono::Local::now();
-        let raw_classifier = LLMBinaryClassifierContext::builder(model, instruction)
-            .result_property(before_date_property)
-            .result_property(after_date_property)
-            .additional_information(format!(
-                "The date must be in the format YYYY-MM-DD.\n\nThe current datetime is: {}",
-                current_datetime.format("%Y-%m-%d").to_string()
-            ))
-            .build();
-        Self { raw_classifier }
+impl RuleBasedDateTimeMentionClassifier {
+    pub fn new(tags: Vec<Tag>) -> Self {
+        Self {
+            tags: tags
+                .into_iter()
+                .map(|tag| (tag.name.to_lowercase(), tag.date))
+                .collect(),
+        }
+    }
+
+    pub fn find_date_range<Tz>(&self, query: &str, now: &DateTime<Tz>) -> Option<DateRange>
+    where
+        Tz: TimeZone,
+    {
+        find_date_range(query, now, &self.tags)
+    }
+
+    pub fn find_date_phrases<Tz>(
+        &self,
+        query: &str,
+        now: &DateTime<Tz>,
+    ) -> (Option<DateRange>, Vec<Range<usize>>)
+    where
+        Tz: TimeZone,
+    {
+        find_date_phrases(query, now, &self.tags)
     }
 }
 
-impl BinaryClassifier<(Option<DateTime<Utc>>, Option<DateTime<Utc>>)>
-    for DateTimeMentionClassifier
-{
+impl BinaryClassifier<DateRange> for RuleBasedDateTimeMentionClassifier {
     async fn classify(
         &self,
         query: String,
-    ) -> Result<BinaryClassificationResult<(Option<DateTime<Utc>>, Option<DateTime<Utc>>)>, ChatError>
-    {
-        let result = self.raw_classifier.raw_classification(query).await;
-        #[derive(Debug, Serialize, Deserialize)]
-        struct RawResult {
-            classification: bool,
-            since: Option<String>,
-            until: Option<String>,
-        }
-        match result {
-            Ok(tool_call) => {
-                let result =
-                    serde_json::from_str::<RawResult>(tool_call.function.arguments.as_str());
-                match result {
-                    Ok(result) => {
-                        if result.classification {
-                            match (result.since, result.until) {
-                                (Some(since), Some(until)) => {
-                                    let since = parse_datetime(since.as_str());
-                                    let until = parse_datetime(until.as_str());
-                                    let mut since_datetime: Option<DateTime<Utc>> = None;
-                                    let mut until_datetime: Option<DateTime<Utc>> = None;
-                                    if let Ok(since) = since {
-                                        since_datetime = Some(since);
-                                    }
-                                    if let Ok(until) = until {
-                                        until_datetime = Some(until);
-                                    }
-                                    Ok(BinaryClassificationResult {
-                                        classification: true,
-                                        content: Some((since_datetime, until_datetime)),
-                                    })
-                                }
-                                _ => {
-                                    return Ok(BinaryClassificationResult {
-                                        classification: false,
-                                        content: None,
-                                    })
-                                }
-                            }
-                        } else {
-                            Ok(BinaryClassificationResult {
-                                classification: false,
-                                content: None,
-                            })
-                        }
-                    }
-                    Err(e) => Err(ChatError::from(e)),
+    ) -> Result<BinaryClassificationResult<DateRange>, ChatError> {
+        let date_range = self.find_date_range(query.as_str(), &Local::now());
+        Ok(BinaryClassificationResult {
+            classification: date_range.is_some(),
+            content: date_range,
+        })
+    }
+}
+
+// Words that introduce a path, e.g. "commits touching Cargo.toml" or "the change in src/parser".
+const PATH_TRIGGER_WORDS: [&str; 9] = [
+    "in",
+    "to",
+    "of",
+    "under",
+    "within",
+    "touching",
+    "changing",
+    "modifying",
+    "affecting",
+];
+
+// Finds path mentions without an LLM: words that look like paths ("src/parser", "Cargo.toml" or
+// "*.rs") and match files of the repository.
+pub struct RuleBasedPathMentionClassifier {
+    files: Vec<String>,
+}
+
+impl RuleBasedPathMentionClassifier {
+    pub fn new(files: Vec<String>) -> Self {
+        Self { files }
+    }
+
+    // Returns the path patterns, e.g. "in src/parser" or "excluding docs/".
+    pub fn find_path_mentions(&self, query: &str) -> Vec<Mention> {
+        let words = split_query_words(query);
+        let mut mentions = Vec::new();
+        for (i, word) in words.iter().enumerate() {
+            if !word.contains(['/', '.']) && !is_glob(word) {
+                continue;
+            }
+            if let Some(pattern) = resolve_path_mention(word, &self.files) {
+                let mut start = match i.checked_sub(1) {
+                    Some(j) if PATH_TRIGGER_WORDS.contains(&words[j].as_str()) => j,
+                    _ => i,
+                };
+                let negated = start > 0 && NEGATION_WORDS.contains(&words[start - 1].as_str());
+                if negated {
+                    start -= 1;
                 }
+                mentions.push(Mention {
+                    value: pattern,
+                    phrase: start..i + 1,
+                    negated,
+                });
             }
-            Err(e) => Err(e),
         }
+        mentions
     }
 }
 
-fn parse_datetime(s: &str) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
-    let datetime = DateTime::parse_from_rfc2822(s)?.with_timezone(&Utc);
-    Ok(datetime)
+impl BinaryClassifier<Vec<String>> for RuleBasedPathMentionClassifier {
+    async fn classify(
+        &self,
+        query: String,
+    ) -> Result<BinaryClassificationResult<Vec<String>>, ChatError> {
+        let paths: Vec<String> = self
+            .find_path_mentions(query.as_str())
+            .into_iter()
+            .filter(|mention| !mention.negated)
+            .map(|mention| mention.value)
+            .collect();
+        Ok(BinaryClassificationResult {
+            classification: !paths.is_empty(),
+            content: (!paths.is_empty()).then_some(paths),
+        })
+    }
 }
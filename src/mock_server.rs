@@ -0,0 +1,173 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    // how long the server waits before it answers, e.g. to run into the timeout of the client
+    pub delay: Duration,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, body: &str) -> MockResponse {
+        MockResponse {
+            status,
+            headers: Vec::new(),
+            body: body.to_string(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+// A request as the server received it.
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub line: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockRequest {
+    // the value of the header with the given name, compared case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// A local HTTP server for tests that answers the requests with the given responses in order, one
+// connection per request, and records the requests.
+// Requests after the last response get a 500. "{url}" in a header value is replaced with the URL of
+// the server, e.g. for the Link header of a paginated response.
+pub struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockServer {
+    pub async fn start(responses: Vec<MockResponse>) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let server_requests = requests.clone();
+        let server_url = url.clone();
+        tokio::spawn(async move {
+            let mut responses = responses.into_iter().map(|mut response| {
+                for (_, value) in response.headers.iter_mut() {
+                    *value = value.replace("{url}", server_url.as_str());
+                }
+                response
+            });
+            while let Ok((stream, _)) = listener.accept().await {
+                let response = responses
+                    .next()
+                    .unwrap_or_else(|| MockResponse::new(500, "no more responses"));
+                let requests = server_requests.clone();
+                tokio::spawn(async move {
+                    let mut stream = stream;
+                    if let Some(request) = read_request(&mut stream).await {
+                        requests.lock().unwrap().push(request);
+                        write_response(&mut stream, response).await;
+                    }
+                });
+            }
+        });
+        MockServer { url, requests }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    // the request lines, e.g. "GET /issues?page=2 HTTP/1.1"
+    pub fn requests(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| request.line.clone())
+            .collect()
+    }
+
+    pub fn request(&self, i: usize) -> MockRequest {
+        self.requests.lock().unwrap()[i].clone()
+    }
+
+    pub fn num_requests(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<MockRequest> {
+    let mut raw = Vec::new();
+    let mut buf = [0u8; 4096];
+    let header_end = loop {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        raw.extend_from_slice(&buf[..n]);
+        if let Some(i) = raw.windows(4).position(|window| window == b"\r\n\r\n") {
+            break i + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&raw[..header_end]).to_string();
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    while raw.len() < header_end + content_length {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        raw.extend_from_slice(&buf[..n]);
+    }
+    let mut lines = head.lines();
+    let line = lines.next()?.to_string();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.to_string(), value.trim().to_string()))
+        .collect();
+    let body = String::from_utf8_lossy(&raw[header_end..]).to_string();
+    Some(MockRequest {
+        line,
+        headers,
+        body,
+    })
+}
+
+async fn write_response(stream: &mut TcpStream, response: MockResponse) {
+    tokio::time::sleep(response.delay).await;
+    let mut raw_response = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+    for (name, value) in response.headers.iter() {
+        raw_response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    raw_response.push_str("\r\n");
+    raw_response.push_str(&response.body);
+    // the client may have given up already, e.g. after a timeout
+    let _ = stream.write_all(raw_response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
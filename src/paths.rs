@@ -0,0 +1,291 @@
+const GLOB_CHARS: [char; 3] = ['*', '?', '['];
+
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(GLOB_CHARS)
+}
+
+// Whether a repository path is matched by a pattern, like a git pathspec with glob magic: "*"
+// and "?" do not match "/", "**" matches any number of directories and a pattern also matches
+// everything below the directories that it matches, e.g. "src/parser" matches
+// "src/parser/tokenizer.rs". As in .gitignore, a pattern without a slash matches in any
+// directory, e.g. "*.rs". Paths are compared case-insensitively, like the mentions that the
+// patterns are resolved from (see `resolve_path_mention`).
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    let pattern = anchor_pattern(pattern);
+    if pattern.is_empty() {
+        return false;
+    }
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let path: Vec<char> = path.to_lowercase().chars().collect();
+    if glob_matches(&pattern, &path) {
+        return true;
+    }
+    path.iter()
+        .enumerate()
+        .filter(|(_, c)| **c == '/')
+        .any(|(i, _)| glob_matches(&pattern, &path[..i]))
+}
+
+// Resolves a path as it is mentioned in a query against the files of the repository (`files`) to a
+// pattern for `path_matches`. Mentions are compared case-insensitively and can be a path,
+// a directory, a file name or a glob. A file name resolves to the bare name, which matches the
+// file in every directory that has one. Returns None if the mention matches no file.
+pub fn resolve_path_mention(mention: &str, files: &[String]) -> Option<String> {
+    let mention = normalize_path(mention.trim_matches(|c: char| "`'\"".contains(c)));
+    if mention.is_empty() {
+        return None;
+    }
+    let lowercase_mention = mention.to_lowercase();
+    if is_glob(mention) {
+        return files
+            .iter()
+            .any(|file| path_matches(lowercase_mention.as_str(), file.to_lowercase().as_str()))
+            .then(|| mention.to_string());
+    }
+    let directory_prefix = format!("{}/", lowercase_mention);
+    let num_directory_segments = mention.split('/').count();
+    let mut file_name: Option<&str> = None;
+    for file in files {
+        let lowercase_file = file.to_lowercase();
+        if lowercase_file == lowercase_mention {
+            return Some(file.clone());
+        }
+        if lowercase_file.starts_with(directory_prefix.as_str()) {
+            // with the casing of the repository, by segment as lowercasing can change the length
+            // of a name
+            let segments: Vec<&str> = file.split('/').take(num_directory_segments).collect();
+            return Some(segments.join("/"));
+        }
+        if let Some(name) = file.rsplit('/').next() {
+            // the same whichever order the files are in
+            if name.to_lowercase() == lowercase_mention && file_name.is_none_or(|n| name < n) {
+                file_name = Some(name);
+            }
+        }
+    }
+    file_name.map(String::from)
+}
+
+fn anchor_pattern(pattern: &str) -> String {
+    let pattern = normalize_path(pattern);
+    if pattern.is_empty() || pattern.contains('/') {
+        pattern.to_string()
+    } else {
+        format!("**/{}", pattern)
+    }
+}
+
+fn normalize_path(path: &str) -> &str {
+    path.trim_start_matches("./").trim_end_matches('/')
+}
+
+fn glob_matches(pattern: &[char], path: &[char]) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => match pattern.get(2) {
+            // "**/" matches any number of directories, including none
+            Some('/') => (0..=path.len())
+                .any(|i| (i == 0 || path[i - 1] == '/') && glob_matches(&pattern[3..], &path[i..])),
+            _ => (0..=path.len()).any(|i| glob_matches(&pattern[2..], &path[i..])),
+        },
+        Some('*') => {
+            for i in 0..=path.len() {
+                if glob_matches(&pattern[1..], &path[i..]) {
+                    return true;
+                }
+                if i < path.len() && path[i] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => match path.first() {
+            Some(c) if *c != '/' => glob_matches(&pattern[1..], &path[1..]),
+            _ => false,
+        },
+        Some('[') => {
+            let end = match pattern.iter().position(|c| *c == ']') {
+                Some(end) if end > 1 => end,
+                _ => return path.first() == Some(&'[') && glob_matches(&pattern[1..], &path[1..]),
+            };
+            let (negated, class) = match pattern[1] {
+                '!' | '^' => (true, &pattern[2..end]),
+                _ => (false, &pattern[1..end]),
+            };
+            match path.first() {
+                Some(c) if *c != '/' && class_contains(class, *c) != negated => {
+                    glob_matches(&pattern[end + 1..], &path[1..])
+                }
+                _ => false,
+            }
+        }
+        Some(c) => path.first() == Some(c) && glob_matches(&pattern[1..], &path[1..]),
+    }
+}
+
+// e.g. "a-z_" contains all lowercase letters and "_"
+fn class_contains(class: &[char], c: char) -> bool {
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            if class[i] <= c && c <= class[i + 2] {
+                return true;
+            }
+            i += 3;
+        } else {
+            if class[i] == c {
+                return true;
+            }
+            i += 1;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> Vec<String> {
+        [
+            "Cargo.toml",
+            "README.md",
+            "src/main.rs",
+            "src/parser/mod.rs",
+            "src/parser/tokenizer.rs",
+            "src/Lexer/mod.rs",
+            "src/Lexer/Token.rs",
+            "docs/Überblick/Einführung.md",
+            "tests/parser_test.rs",
+        ]
+        .iter()
+        .map(|file| file.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_star() {
+        assert!(path_matches("src/*.rs", "src/main.rs"));
+        // "*" does not cross directories
+        assert!(!path_matches("src/*.rs", "src/parser/mod.rs"));
+        assert!(path_matches("src/*/mod.rs", "src/parser/mod.rs"));
+        assert!(path_matches("src/parser/*", "src/parser/mod.rs"));
+        // without a slash the pattern matches in any directory
+        assert!(path_matches("*.rs", "src/parser/mod.rs"));
+        assert!(path_matches("*_test.rs", "tests/parser_test.rs"));
+        assert!(!path_matches("*.rs", "README.md"));
+    }
+
+    #[test]
+    fn test_double_star() {
+        assert!(path_matches("src/**/mod.rs", "src/mod.rs"));
+        assert!(path_matches("src/**/mod.rs", "src/parser/mod.rs"));
+        assert!(path_matches("src/**/mod.rs", "src/a/b/c/mod.rs"));
+        assert!(!path_matches("src/**/mod.rs", "src/parser/modx.rs"));
+        assert!(path_matches("src/**", "src/a/b/c.rs"));
+        assert!(path_matches("**/tokenizer.rs", "src/parser/tokenizer.rs"));
+        assert!(!path_matches("src/**/mod.rs", "tests/mod.rs"));
+    }
+
+    #[test]
+    fn test_question_mark() {
+        assert!(path_matches("src/ma?n.rs", "src/main.rs"));
+        assert!(!path_matches("src/ma?n.rs", "src/man.rs"));
+        // "?" does not match a slash
+        assert!(!path_matches("src?main.rs", "src/main.rs"));
+    }
+
+    #[test]
+    fn test_character_classes() {
+        assert!(path_matches("v[0-9].txt", "v1.txt"));
+        assert!(!path_matches("v[0-9].txt", "vx.txt"));
+        assert!(path_matches("[a-c_]*.rs", "b.rs"));
+        assert!(path_matches("[a-c_]*.rs", "_private.rs"));
+        assert!(!path_matches("[a-c_]*.rs", "main.rs"));
+        assert!(path_matches("[!m]*.rs", "lib.rs"));
+        assert!(!path_matches("[!m]*.rs", "main.rs"));
+        assert!(path_matches("[^m]*.rs", "lib.rs"));
+        // a class never matches a slash, even when negated
+        assert!(!path_matches("src[!x]main.rs", "src/main.rs"));
+        // an unterminated class is a literal "["
+        assert!(path_matches("[a.rs", "[a.rs"));
+        assert!(!path_matches("[].rs", "a.rs"));
+    }
+
+    #[test]
+    fn test_directory_prefixes() {
+        assert!(path_matches("src/parser", "src/parser/tokenizer.rs"));
+        assert!(path_matches("./src/parser/", "src/parser/tokenizer.rs"));
+        assert!(path_matches("src", "src/parser/mod.rs"));
+        // only whole directories
+        assert!(!path_matches("src/pars", "src/parser/mod.rs"));
+        assert!(path_matches("src/p*", "src/parser/mod.rs"));
+        assert!(path_matches("parser", "src/parser/mod.rs"));
+        assert!(!path_matches("", "src/main.rs"));
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        assert!(path_matches("src/lexer/*.rs", "src/Lexer/Token.rs"));
+        assert!(path_matches("SRC/LEXER", "src/Lexer/Token.rs"));
+        assert!(path_matches("[A-Z]*.MD", "readme.md"));
+    }
+
+    #[test]
+    fn test_resolve_path_mention() {
+        let files = files();
+        let resolve = |mention: &str| resolve_path_mention(mention, &files);
+        assert_eq!(resolve("src/main.rs"), Some(String::from("src/main.rs")));
+        assert_eq!(resolve("`readme.md`"), Some(String::from("README.md")));
+        // directories with the casing of the repository
+        assert_eq!(resolve("src/lexer/"), Some(String::from("src/Lexer")));
+        assert_eq!(resolve("./SRC/parser"), Some(String::from("src/parser")));
+        assert_eq!(resolve("tokenizer.rs"), Some(String::from("tokenizer.rs")));
+        assert_eq!(resolve("src/nothing.rs"), None);
+        assert_eq!(resolve("''"), None);
+    }
+
+    #[test]
+    fn test_resolve_non_ascii_directory() {
+        let files = files();
+        let directory = resolve_path_mention("DOCS/ÜBERBLICK", &files).unwrap();
+        assert_eq!(directory, "docs/Überblick");
+        assert!(path_matches(&directory, "docs/Überblick/Einführung.md"));
+        // lowercasing "İ" adds a combining dot, so the mention is longer than the path
+        let files = vec![String::from("İstanbul/notes.md")];
+        assert_eq!(
+            resolve_path_mention("i\u{307}stanbul", &files),
+            Some(String::from("İstanbul"))
+        );
+    }
+
+    #[test]
+    fn test_resolve_ambiguous_file_name() {
+        let files = files();
+        // mod.rs is in two directories, so the bare name matches both
+        let pattern = resolve_path_mention("MOD.RS", &files).unwrap();
+        assert_eq!(pattern, "mod.rs");
+        assert!(path_matches(&pattern, "src/parser/mod.rs"));
+        assert!(path_matches(&pattern, "src/Lexer/mod.rs"));
+        let mut reversed = files.clone();
+        reversed.reverse();
+        assert_eq!(resolve_path_mention("MOD.RS", &reversed).unwrap(), pattern);
+        // with different casings the result does not depend on the order of the files
+        let files = vec![String::from("a/Makefile"), String::from("b/makefile")];
+        let mut reversed = files.clone();
+        reversed.reverse();
+        assert_eq!(
+            resolve_path_mention("makefile", &files),
+            resolve_path_mention("makefile", &reversed)
+        );
+    }
+
+    #[test]
+    fn test_resolve_glob() {
+        let files = files();
+        // offline queries are lowercased before the mentions are resolved
+        let pattern = resolve_path_mention("src/lexer/*.rs", &files).unwrap();
+        assert!(path_matches(&pattern, "src/Lexer/Token.rs"));
+        assert_eq!(resolve_path_mention("src/**/*.py", &files), None);
+    }
+}
@@ -0,0 +1,734 @@
+use crate::http::HttpClient;
+use crate::llm::{
+    ChatError, ChatProvider, ChatRequestBody, ChatResponse, Choice, EmbeddingRequestBody,
+    EmbeddingResponse, FunctionCall, Message, Parameters, ResponseMessage, Role, Tool, ToolCall,
+    Usage,
+};
+use serde::{Deserialize, Serialize};
+
+const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
+const OPENAI_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+const AZURE_OPENAI_API_VERSION: &str = "2024-02-01";
+const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com";
+const ANTHROPIC_API_VERSION: &str = "2023-06-01";
+pub const DEFAULT_ANTHROPIC_MODEL: &str = "claude-3-5-sonnet-latest";
+// the classifiers only ask for a single tool call, so this is generous
+const ANTHROPIC_MAX_TOKENS: u32 = 1024;
+const OLLAMA_API_BASE: &str = "http://localhost:11434";
+pub const DEFAULT_OLLAMA_MODEL: &str = "llama3.1";
+const OLLAMA_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
+// Any endpoint that speaks the OpenAI chat completions API (OpenAI itself, vLLM, LM Studio, ...).
+#[derive(Debug, Clone)]
+pub struct OpenAIProvider {
+    api_key: String,
+    model: String,
+    base_url: String,
+    embedding_model: String,
+    http: HttpClient,
+}
+
+impl OpenAIProvider {
+    pub fn new(api_key: String, model: String, http: HttpClient) -> Self {
+        Self {
+            api_key,
+            model,
+            base_url: String::from(OPENAI_API_BASE),
+            embedding_model: String::from(OPENAI_EMBEDDING_MODEL),
+            http,
+        }
+    }
+
+    pub fn base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn embedding_model(mut self, embedding_model: String) -> Self {
+        self.embedding_model = embedding_model;
+        self
+    }
+
+    pub async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, ChatError> {
+        let body = EmbeddingRequestBody {
+            model: self.embedding_model.clone(),
+            input: inputs,
+        };
+        let request = self
+            .http
+            .post(format!("{}/embeddings", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&body);
+        let mut embedding_response = self.http.send_json::<EmbeddingResponse>(request).await?;
+        // the API does not guarantee that the embeddings are returned in the input order
+        embedding_response
+            .data
+            .sort_by_key(|embedding| embedding.index);
+        Ok(embedding_response
+            .data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+
+    // e.g. "openai-text-embedding-3-small-api.openai.com-v1", as other endpoints and embedding
+    // models return vectors that cannot be compared
+    pub fn name(&self) -> String {
+        let endpoint = self
+            .base_url
+            .split_once("://")
+            .map_or(self.base_url.as_str(), |(_, endpoint)| endpoint);
+        format!("openai-{}-{}", self.embedding_model, endpoint)
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect()
+    }
+}
+
+impl ChatProvider for OpenAIProvider {
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        tools: Option<Vec<Tool>>,
+        temperature: f64,
+    ) -> Result<ChatResponse, ChatError> {
+        let body = ChatRequestBody {
+            model: self.model.clone(),
+            messages,
+            temperature,
+            tools,
+        };
+        let request = self
+            .http
+            .post(format!("{}/chat/completions", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&body);
+        self.http.send_json(request).await
+    }
+}
+
+// Azure OpenAI addresses models by deployment name and authenticates with an `api-key` header.
+#[derive(Debug, Clone)]
+pub struct AzureOpenAIProvider {
+    api_key: String,
+    // e.g. https://my-resource.openai.azure.com
+    endpoint: String,
+    deployment: String,
+    api_version: String,
+    http: HttpClient,
+}
+
+impl AzureOpenAIProvider {
+    pub fn new(api_key: String, endpoint: String, deployment: String, http: HttpClient) -> Self {
+        Self {
+            api_key,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            deployment,
+            api_version: String::from(AZURE_OPENAI_API_VERSION),
+            http,
+        }
+    }
+
+    pub fn api_version(mut self, api_version: String) -> Self {
+        self.api_version = api_version;
+        self
+    }
+}
+
+impl ChatProvider for AzureOpenAIProvider {
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        tools: Option<Vec<Tool>>,
+        temperature: f64,
+    ) -> Result<ChatResponse, ChatError> {
+        let body = ChatRequestBody {
+            // ignored by Azure, the deployment determines the model
+            model: self.deployment.clone(),
+            messages,
+            temperature,
+            tools,
+        };
+        let request = self
+            .http
+            .post(format!(
+                "{}/openai/deployments/{}/chat/completions",
+                self.endpoint, self.deployment
+            ))
+            .query(&[("api-version", self.api_version.as_str())])
+            .header("api-key", &self.api_key)
+            .json(&body);
+        self.http.send_json(request).await
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnthropicProvider {
+    api_key: String,
+    model: String,
+    base_url: String,
+    http: HttpClient,
+}
+
+impl AnthropicProvider {
+    pub fn new(api_key: String, model: String, http: HttpClient) -> Self {
+        Self {
+            api_key,
+            model,
+            base_url: String::from(ANTHROPIC_API_BASE),
+            http,
+        }
+    }
+
+    pub fn base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AnthropicRequestBody {
+    model: String,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<AnthropicMessage>,
+    temperature: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<AnthropicTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<AnthropicToolChoice>,
+}
+
+#[derive(Debug, Serialize)]
+struct AnthropicMessage {
+    role: Role,
+    content: Vec<AnthropicContentBlock>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicContentBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+    },
+    // e.g. thinking blocks, which are not used here
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Serialize)]
+struct AnthropicTool {
+    name: String,
+    description: String,
+    input_schema: Parameters,
+}
+
+#[derive(Debug, Serialize)]
+struct AnthropicToolChoice {
+    r#type: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicResponse {
+    content: Vec<AnthropicContentBlock>,
+    usage: AnthropicUsage,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicUsage {
+    input_tokens: u32,
+    output_tokens: u32,
+}
+
+impl ChatProvider for AnthropicProvider {
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        tools: Option<Vec<Tool>>,
+        temperature: f64,
+    ) -> Result<ChatResponse, ChatError> {
+        // the messages API takes the system prompt separately and tool results as user content
+        let mut system: Option<String> = None;
+        let mut anthropic_messages = Vec::with_capacity(messages.len());
+        for message in messages {
+            match message.role {
+                Role::System => {
+                    system = Some(match system {
+                        Some(system) => format!("{}\n\n{}", system, message.content),
+                        None => message.content,
+                    });
+                }
+                Role::Tool => anthropic_messages.push(AnthropicMessage {
+                    role: Role::User,
+                    content: vec![AnthropicContentBlock::ToolResult {
+                        tool_use_id: message.tool_call_id.unwrap_or_default(),
+                        content: message.content,
+                    }],
+                }),
+                role => anthropic_messages.push(AnthropicMessage {
+                    role,
+                    content: vec![AnthropicContentBlock::Text {
+                        text: message.content,
+                    }],
+                }),
+            }
+        }
+        let tools: Option<Vec<AnthropicTool>> = tools.map(|tools| {
+            tools
+                .into_iter()
+                .map(|tool| AnthropicTool {
+                    name: tool.function.name,
+                    description: tool.function.description,
+                    input_schema: tool.function.parameters,
+                })
+                .collect()
+        });
+        // the callers always expect a tool call when they pass tools
+        let tool_choice = tools.as_ref().map(|_| AnthropicToolChoice {
+            r#type: String::from("any"),
+        });
+        let body = AnthropicRequestBody {
+            model: self.model.clone(),
+            max_tokens: ANTHROPIC_MAX_TOKENS,
+            system,
+            messages: anthropic_messages,
+            temperature,
+            tools,
+            tool_choice,
+        };
+        let request = self
+            .http
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_API_VERSION)
+            .json(&body);
+        let response = self.http.send_json::<AnthropicResponse>(request).await?;
+        let mut content: Option<String> = None;
+        let mut tool_calls = Vec::new();
+        for block in response.content {
+            match block {
+                AnthropicContentBlock::Text { text } => {
+                    content = Some(content.unwrap_or_default() + &text);
+                }
+                AnthropicContentBlock::ToolUse { id, name, input } => tool_calls.push(ToolCall {
+                    id,
+                    r#type: String::from("function"),
+                    function: FunctionCall {
+                        name,
+                        arguments: input.to_string(),
+                    },
+                }),
+                _ => {}
+            }
+        }
+        Ok(ChatResponse {
+            choices: vec![Choice {
+                message: ResponseMessage {
+                    role: Role::Assistant,
+                    content,
+                    tool_calls: if tool_calls.is_empty() {
+                        None
+                    } else {
+                        Some(tool_calls)
+                    },
+                },
+            }],
+            usage: Usage {
+                prompt_tokens: response.usage.input_tokens,
+                completion_tokens: response.usage.output_tokens,
+                total_tokens: response.usage.input_tokens + response.usage.output_tokens,
+            },
+        })
+    }
+}
+
+// A local Ollama server, which needs no API key or network access.
+#[derive(Debug, Clone)]
+pub struct OllamaProvider {
+    model: String,
+    base_url: String,
+    embedding_model: String,
+    http: HttpClient,
+}
+
+impl OllamaProvider {
+    pub fn new(model: String, http: HttpClient) -> Self {
+        Self {
+            model,
+            base_url: String::from(OLLAMA_API_BASE),
+            embedding_model: String::from(OLLAMA_EMBEDDING_MODEL),
+            http,
+        }
+    }
+
+    pub fn base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn embedding_model(mut self, embedding_model: String) -> Self {
+        self.embedding_model = embedding_model;
+        self
+    }
+
+    pub async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, ChatError> {
+        #[derive(Serialize)]
+        struct OllamaEmbedRequestBody {
+            model: String,
+            input: Vec<String>,
+        }
+        #[derive(Deserialize)]
+        struct OllamaEmbedResponse {
+            embeddings: Vec<Vec<f32>>,
+        }
+        let body = OllamaEmbedRequestBody {
+            model: self.embedding_model.clone(),
+            input: inputs,
+        };
+        let request = self
+            .http
+            .post(format!("{}/api/embed", self.base_url))
+            .json(&body);
+        Ok(self
+            .http
+            .send_json::<OllamaEmbedResponse>(request)
+            .await?
+            .embeddings)
+    }
+
+    pub fn name(&self) -> String {
+        format!("ollama-{}", self.embedding_model)
+    }
+}
+
+impl ChatProvider for OllamaProvider {
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        tools: Option<Vec<Tool>>,
+        temperature: f64,
+    ) -> Result<ChatResponse, ChatError> {
+        #[derive(Serialize)]
+        struct OllamaOptions {
+            temperature: f64,
+        }
+        #[derive(Serialize)]
+        struct OllamaRequestBody {
+            model: String,
+            messages: Vec<Message>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            tools: Option<Vec<Tool>>,
+            stream: bool,
+            options: OllamaOptions,
+        }
+        #[derive(Deserialize)]
+        struct OllamaFunctionCall {
+            name: String,
+            // an object rather than a JSON encoded string as in the OpenAI API
+            arguments: serde_json::Value,
+        }
+        #[derive(Deserialize)]
+        struct OllamaToolCall {
+            function: OllamaFunctionCall,
+        }
+        #[derive(Deserialize)]
+        struct OllamaMessage {
+            content: String,
+            tool_calls: Option<Vec<OllamaToolCall>>,
+        }
+        #[derive(Deserialize)]
+        struct OllamaResponse {
+            message: OllamaMessage,
+            #[serde(default)]
+            prompt_eval_count: u32,
+            #[serde(default)]
+            eval_count: u32,
+        }
+        let body = OllamaRequestBody {
+            model: self.model.clone(),
+            messages,
+            tools,
+            stream: false,
+            options: OllamaOptions { temperature },
+        };
+        let request = self
+            .http
+            .post(format!("{}/api/chat", self.base_url))
+            .json(&body);
+        let response = self.http.send_json::<OllamaResponse>(request).await?;
+        let tool_calls = response.message.tool_calls.map(|tool_calls| {
+            tool_calls
+                .into_iter()
+                .enumerate()
+                .map(|(i, tool_call)| ToolCall {
+                    // Ollama does not assign ids to tool calls
+                    id: format!("call_{}", i),
+                    r#type: String::from("function"),
+                    function: FunctionCall {
+                        name: tool_call.function.name,
+                        arguments: tool_call.function.arguments.to_string(),
+                    },
+                })
+                .collect()
+        });
+        Ok(ChatResponse {
+            choices: vec![Choice {
+                message: ResponseMessage {
+                    role: Role::Assistant,
+                    content: Some(response.message.content),
+                    tool_calls,
+                },
+            }],
+            usage: Usage {
+                prompt_tokens: response.prompt_eval_count,
+                completion_tokens: response.eval_count,
+                total_tokens: response.prompt_eval_count + response.eval_count,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::RetryConfig;
+    use crate::llm::Function;
+    use crate::mock_server::{MockResponse, MockServer};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    fn http() -> HttpClient {
+        HttpClient::new(RetryConfig::default()).unwrap()
+    }
+
+    fn messages() -> Vec<Message> {
+        vec![
+            Message {
+                role: Role::System,
+                content: String::from("You are a classifier."),
+                tool_call_id: None,
+            },
+            Message {
+                role: Role::User,
+                content: String::from("Is this relevant?"),
+                tool_call_id: None,
+            },
+        ]
+    }
+
+    fn tool() -> Tool {
+        Tool {
+            r#type: String::from("function"),
+            function: Function {
+                name: String::from("binary_classification"),
+                description: String::from("Classify the candidate."),
+                parameters: Parameters {
+                    r#type: String::from("object"),
+                    properties: HashMap::new(),
+                    required: vec![String::from("classification")],
+                },
+            },
+        }
+    }
+
+    fn json_body(server: &MockServer, i: usize) -> Value {
+        serde_json::from_str(&server.request(i).body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_chat() {
+        let response = json!({
+            "content": [
+                {"type": "text", "text": "Let me classify this."},
+                {
+                    "type": "tool_use",
+                    "id": "toolu_1",
+                    "name": "binary_classification",
+                    "input": {"classification": true}
+                }
+            ],
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        });
+        let server = MockServer::start(vec![MockResponse::new(200, &response.to_string())]).await;
+        let provider = AnthropicProvider::new(String::from("key"), String::from("claude"), http())
+            .base_url(server.url().to_string());
+        let response = provider
+            .chat(messages(), Some(vec![tool()]), 0.0)
+            .await
+            .unwrap();
+
+        let request = server.request(0);
+        assert_eq!(request.line, "POST /v1/messages HTTP/1.1");
+        assert_eq!(request.header("x-api-key"), Some("key"));
+        assert_eq!(
+            request.header("anthropic-version"),
+            Some(ANTHROPIC_API_VERSION)
+        );
+        let body = json_body(&server, 0);
+        // the system prompt is not one of the messages
+        assert_eq!(body["system"], "You are a classifier.");
+        assert_eq!(
+            body["messages"],
+            json!([{"role": "user", "content": [{"type": "text", "text": "Is this relevant?"}]}])
+        );
+        assert_eq!(body["tools"][0]["name"], "binary_classification");
+        assert_eq!(body["tools"][0]["input_schema"]["type"], "object");
+        assert_eq!(body["tool_choice"], json!({"type": "any"}));
+
+        let message = &response.choices[0].message;
+        assert_eq!(message.content.as_deref(), Some("Let me classify this."));
+        let tool_calls = message.tool_calls.as_ref().unwrap();
+        assert_eq!(tool_calls.len(), 1);
+        assert_eq!(tool_calls[0].id, "toolu_1");
+        assert_eq!(tool_calls[0].function.name, "binary_classification");
+        assert_eq!(
+            serde_json::from_str::<Value>(&tool_calls[0].function.arguments).unwrap(),
+            json!({"classification": true})
+        );
+        assert_eq!(response.usage.total_tokens, 15);
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_chat_without_tools() {
+        let response = json!({
+            "content": [{"type": "text", "text": "Hello"}],
+            "usage": {"input_tokens": 1, "output_tokens": 1}
+        });
+        let server = MockServer::start(vec![MockResponse::new(200, &response.to_string())]).await;
+        let provider = AnthropicProvider::new(String::from("key"), String::from("claude"), http())
+            .base_url(server.url().to_string());
+        let response = provider.chat(messages(), None, 0.0).await.unwrap();
+
+        let body = json_body(&server, 0);
+        assert!(body.get("tools").is_none());
+        assert!(body.get("tool_choice").is_none());
+        assert!(response.choices[0].message.tool_calls.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ollama_chat() {
+        let response = json!({
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{
+                    "function": {
+                        "name": "binary_classification",
+                        "arguments": {"classification": false}
+                    }
+                }]
+            },
+            "prompt_eval_count": 7,
+            "eval_count": 3
+        });
+        let server = MockServer::start(vec![MockResponse::new(200, &response.to_string())]).await;
+        let provider = OllamaProvider::new(String::from("llama3.1"), http())
+            .base_url(server.url().to_string());
+        let response = provider
+            .chat(messages(), Some(vec![tool()]), 0.0)
+            .await
+            .unwrap();
+
+        assert_eq!(server.requests(), vec!["POST /api/chat HTTP/1.1"]);
+        let body = json_body(&server, 0);
+        assert_eq!(body["model"], "llama3.1");
+        assert_eq!(body["stream"], false);
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(
+            body["tools"][0]["function"]["name"],
+            "binary_classification"
+        );
+
+        let tool_calls = response.choices[0].message.tool_calls.as_ref().unwrap();
+        assert_eq!(tool_calls[0].id, "call_0");
+        assert_eq!(
+            serde_json::from_str::<Value>(&tool_calls[0].function.arguments).unwrap(),
+            json!({"classification": false})
+        );
+        assert_eq!(response.usage.total_tokens, 10);
+    }
+
+    #[tokio::test]
+    async fn test_ollama_embed() {
+        let response = json!({"embeddings": [[0.1, 0.2], [0.3, 0.4]]});
+        let server = MockServer::start(vec![MockResponse::new(200, &response.to_string())]).await;
+        let provider = OllamaProvider::new(String::from("llama3.1"), http())
+            .base_url(server.url().to_string());
+        let embeddings = provider
+            .embed(vec![String::from("a"), String::from("b")])
+            .await
+            .unwrap();
+
+        assert_eq!(server.requests(), vec!["POST /api/embed HTTP/1.1"]);
+        assert_eq!(
+            json_body(&server, 0),
+            json!({"model": OLLAMA_EMBEDDING_MODEL, "input": ["a", "b"]})
+        );
+        assert_eq!(embeddings, vec![vec![0.1, 0.2], vec![0.3, 0.4]]);
+    }
+
+    #[tokio::test]
+    async fn test_azure_chat() {
+        let response = json!({
+            "choices": [{"message": {"role": "assistant", "content": "Hello", "tool_calls": null}}],
+            "usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2}
+        });
+        let server = MockServer::start(vec![MockResponse::new(200, &response.to_string())]).await;
+        let provider = AzureOpenAIProvider::new(
+            String::from("key"),
+            format!("{}/", server.url()),
+            String::from("gpt-4o"),
+            http(),
+        )
+        .api_version(String::from("2024-06-01"));
+        let response = provider.chat(messages(), None, 0.0).await.unwrap();
+
+        let request = server.request(0);
+        assert_eq!(
+            request.line,
+            "POST /openai/deployments/gpt-4o/chat/completions?api-version=2024-06-01 HTTP/1.1"
+        );
+        assert_eq!(request.header("api-key"), Some("key"));
+        assert_eq!(request.header("authorization"), None);
+        assert_eq!(
+            response.choices[0].message.content.as_deref(),
+            Some("Hello")
+        );
+    }
+
+    #[test]
+    fn test_openai_name() {
+        let provider = OpenAIProvider::new(
+            String::new(),
+            String::from("gpt-4"),
+            HttpClient::new(RetryConfig::default()).unwrap(),
+        );
+        assert_eq!(
+            provider.name(),
+            "openai-text-embedding-3-small-api.openai.com-v1"
+        );
+        let provider = provider
+            .base_url(String::from("http://localhost:8000/v1/"))
+            .embedding_model(String::from("BAAI/bge-small-en"));
+        assert_eq!(
+            provider.name(),
+            "openai-BAAI-bge-small-en-localhost-8000-v1"
+        );
+    }
+}
@@ -0,0 +1,527 @@
+use crate::authors::Identity;
+use crate::classifier::LLMBinaryClassifierContext;
+use crate::dates::parse_date;
+use crate::git::DateRange;
+use crate::github::IssueState;
+use crate::llm::{ChatError, ChatModel, Property};
+use crate::mention_classifiers::{
+    RuleBasedAuthorMentionClassifier, RuleBasedDateTimeMentionClassifier,
+    RuleBasedPathMentionClassifier, NEGATION_WORDS,
+};
+use crate::query_syntax::TextQuery;
+use crate::search_result::ResultKind;
+use crate::utils::split_query_words;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::future::Future;
+use std::ops::Range;
+
+pub trait QueryParser {
+    fn parse(&self, query: String) -> impl Future<Output = Result<ParsedQuery, ChatError>> + Send;
+}
+
+// A query split into the text that is searched for and the filters on the results, e.g. "johns
+// commits on the new classification head from last week" is searched for as "new classification
+// head" among the commits of John from last week.
+#[derive(Debug, Clone, Default)]
+pub struct ParsedQuery {
+    // the query without the filter phrases, never empty
+    pub search_text: String,
+    // author mentions as they are written, e.g. "johns" or "@octocat", see `authors::AuthorResolver`
+    pub authors: Vec<String>,
+    pub date_range: Option<DateRange>,
+    // file paths, directories or glob patterns
+    pub paths: Vec<String>,
+    pub kind: Option<ResultKind>,
+    pub state: Option<IssueState>,
+    pub labels: Vec<String>,
+    // negated filters, e.g. "not by the bot", "excluding docs/", "without label wontfix" or
+    // "except dependency bumps"
+    pub excluded_authors: Vec<String>,
+    pub excluded_paths: Vec<String>,
+    pub excluded_labels: Vec<String>,
+    pub excluded_keywords: Vec<String>,
+    // only merged pull requests, e.g. `is:merged`
+    pub merged: bool,
+    // phrases and boolean combinations of terms that the text of a result must match, from the
+    // query syntax
+    pub text_filter: Option<TextQuery>,
+}
+
+impl ParsedQuery {
+    // A query without any filters.
+    pub fn unfiltered(query: String) -> ParsedQuery {
+        ParsedQuery {
+            search_text: query,
+            ..ParsedQuery::default()
+        }
+    }
+
+    // Whether the query filters the results at all, e.g. with `author:alice` even when the
+    // filters in natural language are not extracted.
+    pub fn has_filters(&self) -> bool {
+        !self.authors.is_empty()
+            || self.date_range.is_some()
+            || !self.paths.is_empty()
+            || !self.excluded_authors.is_empty()
+            || !self.excluded_paths.is_empty()
+            || !self.excluded_keywords.is_empty()
+            || self.text_filter.is_some()
+    }
+
+    // Adds the filters that were extracted from the free text of a query to the filters that it
+    // states explicitly, which take precedence. The search text is the extracted one, without the
+    // filter phrases.
+    pub fn with_extracted(self, extracted: ParsedQuery) -> ParsedQuery {
+        let or = |explicit: Vec<String>, extracted: Vec<String>| {
+            if explicit.is_empty() {
+                extracted
+            } else {
+                explicit
+            }
+        };
+        let mut excluded_keywords = self.excluded_keywords;
+        excluded_keywords.extend(extracted.excluded_keywords);
+        ParsedQuery {
+            search_text: extracted.search_text,
+            authors: or(self.authors, extracted.authors),
+            date_range: self.date_range.or(extracted.date_range),
+            paths: or(self.paths, extracted.paths),
+            kind: self.kind.or(extracted.kind),
+            state: self.state.or(extracted.state),
+            labels: or(self.labels, extracted.labels),
+            excluded_authors: or(self.excluded_authors, extracted.excluded_authors),
+            excluded_paths: or(self.excluded_paths, extracted.excluded_paths),
+            excluded_labels: or(self.excluded_labels, extracted.excluded_labels),
+            excluded_keywords,
+            merged: self.merged || extracted.merged,
+            text_filter: self.text_filter.or(extracted.text_filter),
+        }
+    }
+}
+
+// Extracts all filters with a single function call.
+pub struct LLMQueryParser {
+    raw_classifier: LLMBinaryClassifierContext<ChatModel>,
+}
+
+impl LLMQueryParser {
+    pub fn new(model: ChatModel, identities: &[Identity]) -> Self {
+        let instruction = String::from(
+            "Determine if the user's query filters the results by author, date, file path, kind of result, state, label or keyword, and separate the filters from the text that the user is searching for. Filters can have several values (e.g. \"by alice or bob\") and can be negated (e.g. \"not by bob\").",
+        );
+        let result_properties = [
+            (
+                "search_text",
+                "The query without the words that express the filters (e.g. \"by john\", \"last week\", \"issues\", \"in src/parser\"), keeping only the words that describe what is searched for",
+            ),
+            (
+                "authors",
+                "The comma-separated names, emails or GitHub logins of the authors that the user is filtering by, preferably as listed in the author list",
+            ),
+            (
+                "since",
+                "The YYYY-MM-DD date from which on the user wants results (omit if the range has no start)",
+            ),
+            (
+                "until",
+                "The YYYY-MM-DD date up to which (inclusive) the user wants results (omit if the range has no end)",
+            ),
+            (
+                "paths",
+                "The comma-separated file paths, directories or glob patterns that the results must touch",
+            ),
+            (
+                "kind",
+                "\"commit\", \"issue\" or \"pull_request\" if the user only wants one kind of result",
+            ),
+            (
+                "state",
+                "\"open\" or \"closed\" if the user only wants issues or pull requests in that state",
+            ),
+            (
+                "labels",
+                "The comma-separated labels that the issues or pull requests must all have",
+            ),
+            (
+                "excluded_authors",
+                "The comma-separated names, emails or GitHub logins of the authors whose results the user does not want (e.g. \"not by the bot\")",
+            ),
+            (
+                "excluded_paths",
+                "The comma-separated file paths, directories or glob patterns that the user does not want results for (e.g. \"excluding docs/\")",
+            ),
+            (
+                "excluded_labels",
+                "The comma-separated labels that the issues or pull requests must not have",
+            ),
+            (
+                "excluded_keywords",
+                "The comma-separated keywords of the results that the user does not want (e.g. \"dependency bump\" in \"except dependency bumps\")",
+            ),
+        ];
+        let mut builder = LLMBinaryClassifierContext::builder(model, instruction);
+        for (name, description) in result_properties {
+            builder = builder.result_property((
+                String::from(name),
+                Property {
+                    r#type: String::from("string"),
+                    description: String::from(description),
+                },
+            ));
+        }
+        let raw_classifier = builder
+            .additional_information(format!(
+                "## Known Authors\n{}\n\n*Authors may be referred to by nicknames, first names, emails or GitHub logins.*\n\nDates must be in the format YYYY-MM-DD.\n\nThe current datetime is: {}",
+                identities
+                    .iter()
+                    .map(|identity| format!("- {}", identity))
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+                Local::now().format("%Y-%m-%d")
+            ))
+            .build();
+        Self { raw_classifier }
+    }
+}
+
+impl QueryParser for LLMQueryParser {
+    async fn parse(&self, query: String) -> Result<ParsedQuery, ChatError> {
+        let tool_call = self
+            .raw_classifier
+            .raw_classification(query.clone())
+            .await?;
+        #[derive(Debug, Serialize, Deserialize)]
+        struct RawResult {
+            classification: bool,
+            search_text: Option<String>,
+            authors: Option<String>,
+            since: Option<String>,
+            until: Option<String>,
+            paths: Option<String>,
+            kind: Option<String>,
+            state: Option<String>,
+            labels: Option<String>,
+            excluded_authors: Option<String>,
+            excluded_paths: Option<String>,
+            excluded_labels: Option<String>,
+            excluded_keywords: Option<String>,
+        }
+        let result = serde_json::from_str::<RawResult>(tool_call.function.arguments.as_str())?;
+        if !result.classification {
+            return Ok(ParsedQuery::unfiltered(query));
+        }
+        // either end of the range can be open
+        let now = Local::now();
+        let since = result
+            .since
+            .and_then(|since| parse_date(since.as_str(), &now))
+            .map(|span| span.start);
+        let until = result
+            .until
+            .and_then(|until| parse_date(until.as_str(), &now))
+            .map(|span| span.end);
+        Ok(ParsedQuery {
+            search_text: result
+                .search_text
+                .map(|search_text| search_text.trim().to_string())
+                .filter(|search_text| !search_text.is_empty())
+                .unwrap_or(query),
+            authors: split_list(result.authors),
+            date_range: match (since, until) {
+                (None, None) => None,
+                date_range => Some(date_range),
+            },
+            paths: split_list(result.paths),
+            // values that are not understood are ignored rather than failing the search
+            kind: result
+                .kind
+                .and_then(|kind| kind.trim().to_lowercase().parse().ok()),
+            state: result
+                .state
+                .and_then(|state| state.trim().to_lowercase().parse().ok()),
+            labels: split_list(result.labels),
+            excluded_authors: split_list(result.excluded_authors),
+            excluded_paths: split_list(result.excluded_paths),
+            excluded_labels: split_list(result.excluded_labels),
+            excluded_keywords: split_list(result.excluded_keywords),
+            ..ParsedQuery::default()
+        })
+    }
+}
+
+// Words that name a kind of result after a state, e.g. "open issues about the parser" or "merged
+// PRs". On their own they are searched for, e.g. "crash when the issue tracker opens".
+const KIND_WORDS: [(&str, ResultKind); 5] = [
+    ("issue", ResultKind::Issue),
+    ("issues", ResultKind::Issue),
+    ("pr", ResultKind::PullRequest),
+    ("prs", ResultKind::PullRequest),
+    ("pull requests", ResultKind::PullRequest),
+];
+const MERGED_WORD: &str = "merged";
+// Words that introduce a label, e.g. "issues labeled bug". "label" alone only does after "with"
+// or "without", e.g. "issues with label bug", as it is too common a word otherwise (e.g. "fix
+// label rendering").
+const LABEL_TRIGGER_WORDS: [&str; 2] = ["labeled", "labelled"];
+const LABEL_WORD: &str = "label";
+// Words that exclude the keywords after them, e.g. "except dependency bumps". "not" and "without"
+// are not as they are mostly part of what is searched for, e.g. "crash when file not found".
+const KEYWORD_EXCLUSION_WORDS: [&str; 2] = ["except", "excluding"];
+// Words that end an excluded keyword, e.g. "except dependency bumps in the parser".
+const KEYWORD_STOP_WORDS: [&str; 12] = [
+    "in", "on", "about", "for", "to", "with", "of", "and", "or", "by", "from", "that",
+];
+const MAX_KEYWORD_WORDS: usize = 3;
+
+// Extracts the filters with rules, see `RuleBasedAuthorMentionClassifier`,
+// `RuleBasedDateTimeMentionClassifier` and `RuleBasedPathMentionClassifier`. Kinds and states
+// are only recognized as in "closed issues" or "merged PRs", labels as in "labeled bug", "with
+// label bug" or "not labeled bug" and excluded keywords as in "except dependency bumps".
+pub struct RuleBasedQueryParser {
+    author: RuleBasedAuthorMentionClassifier,
+    datetime: RuleBasedDateTimeMentionClassifier,
+    path: RuleBasedPathMentionClassifier,
+}
+
+impl RuleBasedQueryParser {
+    pub fn new(
+        author: RuleBasedAuthorMentionClassifier,
+        datetime: RuleBasedDateTimeMentionClassifier,
+        path: RuleBasedPathMentionClassifier,
+    ) -> Self {
+        Self {
+            author,
+            datetime,
+            path,
+        }
+    }
+
+    pub fn parse_query(&self, query: &str) -> ParsedQuery {
+        let words = split_query_words(query);
+        let mut phrases: Vec<Range<usize>> = Vec::new();
+        let mut parsed_query = ParsedQuery::default();
+        for mention in self.author.find_author_mentions(query) {
+            if mention.negated {
+                parsed_query.excluded_authors.push(mention.value);
+            } else {
+                parsed_query.authors.push(mention.value);
+            }
+            phrases.push(mention.phrase);
+        }
+        let (date_range, date_phrases) = self.datetime.find_date_phrases(query, &Local::now());
+        parsed_query.date_range = date_range;
+        // a phrase that was not understood as a whole is still searched for
+        if date_range.is_some() {
+            phrases.extend(date_phrases);
+        }
+        for mention in self.path.find_path_mentions(query) {
+            if mention.negated {
+                parsed_query.excluded_paths.push(mention.value);
+            } else {
+                parsed_query.paths.push(mention.value);
+            }
+            phrases.push(mention.phrase);
+        }
+        let is_negation = |i: usize| NEGATION_WORDS.contains(&words[i].as_str());
+        let mut i = 0;
+        while i < words.len() {
+            // "open issues", "closed pull requests" or "merged prs"
+            let state = words[i].parse::<IssueState>().ok();
+            let kind = (state.is_some() || words[i] == MERGED_WORD)
+                .then(|| {
+                    let pair = words
+                        .get(i + 2)
+                        .map(|next| format!("{} {}", words[i + 1], next));
+                    KIND_WORDS.iter().find_map(|(kind_words, kind)| {
+                        if words.get(i + 1).map(String::as_str) == Some(*kind_words) {
+                            Some((*kind, 1))
+                        } else if pair.as_deref() == Some(*kind_words) {
+                            Some((*kind, 2))
+                        } else {
+                            None
+                        }
+                    })
+                })
+                .flatten()
+                .filter(|(kind, _)| state.is_some() || *kind == ResultKind::PullRequest);
+            if let Some((kind, n)) = kind {
+                parsed_query.kind.get_or_insert(kind);
+                match state {
+                    Some(state) => {
+                        parsed_query.state.get_or_insert(state);
+                    }
+                    None => parsed_query.merged = true,
+                }
+                phrases.push(i..i + 1 + n);
+                i += 1 + n;
+                continue;
+            }
+            let is_label_trigger = LABEL_TRIGGER_WORDS.contains(&words[i].as_str())
+                || (words[i] == LABEL_WORD
+                    && i > 0
+                    && ["with", "without"].contains(&words[i - 1].as_str()));
+            if is_label_trigger && i + 1 < words.len() {
+                let negated = i > 0 && is_negation(i - 1);
+                if negated {
+                    parsed_query.excluded_labels.push(words[i + 1].clone());
+                    phrases.push(i - 1..i + 2);
+                } else if words[i] == LABEL_WORD {
+                    parsed_query.labels.push(words[i + 1].clone());
+                    phrases.push(i - 1..i + 2);
+                } else {
+                    parsed_query.labels.push(words[i + 1].clone());
+                    phrases.push(i..i + 2);
+                }
+                i += 2;
+                continue;
+            }
+            i += 1;
+        }
+        // the words after an exclusion that is not part of another filter, e.g. "except dependency
+        // bumps"
+        let consumed: HashSet<usize> = phrases.iter().cloned().flatten().collect();
+        for i in 0..words.len() {
+            if consumed.contains(&i) || !KEYWORD_EXCLUSION_WORDS.contains(&words[i].as_str()) {
+                continue;
+            }
+            let end = (i + 1..words.len())
+                .take(MAX_KEYWORD_WORDS)
+                .take_while(|j| {
+                    !consumed.contains(j) && !KEYWORD_STOP_WORDS.contains(&words[*j].as_str())
+                })
+                .last()
+                .map(|j| j + 1);
+            if let Some(end) = end {
+                parsed_query
+                    .excluded_keywords
+                    .push(words[i + 1..end].join(" "));
+                phrases.push(i..end);
+            }
+        }
+        let removed: HashSet<usize> = phrases.into_iter().flatten().collect();
+        let search_text = words
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !removed.contains(i))
+            .map(|(_, word)| word.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ");
+        // a query that only consists of filters, e.g. "johns commits", is still searched for as is
+        parsed_query.search_text = if search_text.is_empty() {
+            query.to_string()
+        } else {
+            search_text
+        };
+        parsed_query
+    }
+}
+
+impl QueryParser for RuleBasedQueryParser {
+    async fn parse(&self, query: String) -> Result<ParsedQuery, ChatError> {
+        Ok(self.parse_query(query.as_str()))
+    }
+}
+
+fn split_list(list: Option<String>) -> Vec<String> {
+    list.map(|list| {
+        list.split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authors::{AuthorResolver, Mailmap};
+
+    fn parse_query(query: &str) -> ParsedQuery {
+        RuleBasedQueryParser::new(
+            RuleBasedAuthorMentionClassifier::new(AuthorResolver::new(
+                Vec::new(),
+                &Mailmap::default(),
+            )),
+            RuleBasedDateTimeMentionClassifier::new(Vec::new()),
+            RuleBasedPathMentionClassifier::new(Vec::new()),
+        )
+        .parse_query(query)
+    }
+
+    #[test]
+    fn test_not_and_without_are_searched_for() {
+        let parsed_query = parse_query("crash when file not found");
+        assert!(parsed_query.excluded_keywords.is_empty());
+        assert_eq!(parsed_query.search_text, "crash when file not found");
+        let parsed_query = parse_query("build without cache");
+        assert!(parsed_query.excluded_keywords.is_empty());
+        assert_eq!(parsed_query.search_text, "build without cache");
+    }
+
+    #[test]
+    fn test_excluded_keywords() {
+        let parsed_query = parse_query("parser fixes except dependency bumps");
+        assert_eq!(parsed_query.excluded_keywords, vec!["dependency bumps"]);
+        assert_eq!(parsed_query.search_text, "parser fixes");
+        let parsed_query = parse_query("crashes excluding windows");
+        assert_eq!(parsed_query.excluded_keywords, vec!["windows"]);
+    }
+
+    #[test]
+    fn test_label_alone_is_searched_for() {
+        let parsed_query = parse_query("fix label rendering");
+        assert!(parsed_query.labels.is_empty());
+        assert_eq!(parsed_query.search_text, "fix label rendering");
+    }
+
+    #[test]
+    fn test_labels() {
+        let parsed_query = parse_query("issues labeled bug");
+        assert_eq!(parsed_query.labels, vec!["bug"]);
+        let parsed_query = parse_query("issues with label bug");
+        assert_eq!(parsed_query.labels, vec!["bug"]);
+        assert_eq!(parsed_query.search_text, "issues");
+        let parsed_query = parse_query("crash without label wontfix");
+        assert_eq!(parsed_query.excluded_labels, vec!["wontfix"]);
+        assert_eq!(parsed_query.search_text, "crash");
+        let parsed_query = parse_query("crash not labeled wontfix");
+        assert_eq!(parsed_query.excluded_labels, vec!["wontfix"]);
+    }
+
+    #[test]
+    fn test_kind_alone_is_searched_for() {
+        for query in [
+            "crash when the issue tracker opens",
+            "commit message is truncated",
+            "pr template typo",
+            "issues with the parser",
+        ] {
+            let parsed_query = parse_query(query);
+            assert_eq!(parsed_query.kind, None, "{}", query);
+            assert_eq!(parsed_query.state, None, "{}", query);
+            assert_eq!(parsed_query.search_text, query);
+        }
+    }
+
+    #[test]
+    fn test_state_and_kind() {
+        let parsed_query = parse_query("open issues about the parser");
+        assert_eq!(parsed_query.kind, Some(ResultKind::Issue));
+        assert_eq!(parsed_query.state, Some(IssueState::Open));
+        assert_eq!(parsed_query.search_text, "about the parser");
+        let parsed_query = parse_query("closed pull requests for the lexer");
+        assert_eq!(parsed_query.kind, Some(ResultKind::PullRequest));
+        assert_eq!(parsed_query.state, Some(IssueState::Closed));
+        let parsed_query = parse_query("merged PRs that touch the cache");
+        assert_eq!(parsed_query.kind, Some(ResultKind::PullRequest));
+        assert!(parsed_query.merged);
+        assert_eq!(parsed_query.state, None);
+        // merged only applies to pull requests
+        let parsed_query = parse_query("merged issues");
+        assert_eq!(parsed_query.kind, None);
+        assert!(!parsed_query.merged);
+    }
+}
@@ -0,0 +1,665 @@
+use crate::dates::parse_date;
+use crate::query_parser::ParsedQuery;
+use crate::search_result::ResultKind;
+use crate::utils::strip_plural;
+use chrono::{Duration, Local};
+
+// Qualifiers of the query syntax, e.g. `author:alice`. Any other `key:value` word is searched for
+// as is, e.g. "std::io" or a URL.
+const QUALIFIERS: [&str; 9] = [
+    "author", "path", "after", "before", "since", "until", "label", "is", "kind",
+];
+
+// A boolean expression over the text of a result, e.g. `parser AND (lexer OR tokenizer)` or
+// `"exact phrase"`. Terms match like excluded keywords, case-insensitively and ignoring plurals.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextQuery {
+    Term(String),
+    Phrase(String),
+    Not(Box<TextQuery>),
+    And(Vec<TextQuery>),
+    Or(Vec<TextQuery>),
+}
+
+impl TextQuery {
+    // `text` must be lowercase with single spaces between words, see `normalize_text`.
+    pub fn matches(&self, text: &str) -> bool {
+        match self {
+            TextQuery::Term(term) => text.contains(strip_plural(&term.to_lowercase())),
+            TextQuery::Phrase(phrase) => text.contains(normalize_text(phrase).as_str()),
+            TextQuery::Not(query) => !query.matches(text),
+            TextQuery::And(queries) => queries.iter().all(|query| query.matches(text)),
+            TextQuery::Or(queries) => queries.iter().any(|query| query.matches(text)),
+        }
+    }
+
+    // The terms and phrases that are not negated, which are what is ranked.
+    fn positive_words(&self, words: &mut Vec<String>) {
+        match self {
+            TextQuery::Term(text) | TextQuery::Phrase(text) => words.push(text.clone()),
+            TextQuery::Not(_) => {}
+            TextQuery::And(queries) | TextQuery::Or(queries) => {
+                for query in queries {
+                    query.positive_words(words);
+                }
+            }
+        }
+    }
+}
+
+// Lowercases the text and collapses whitespace, so that phrases match across line breaks.
+pub fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+// The filters that a query states with the query syntax, see `parse_query_syntax`.
+#[derive(Debug, Clone, Default)]
+pub struct ExplicitQuery {
+    // the filters of the qualifiers, with the free text of the query as the search text
+    pub parsed_query: ParsedQuery,
+    // with qualifiers the query needs no further parsing, e.g. by an LLM
+    pub has_qualifiers: bool,
+    // qualifiers whose value was not understood and were ignored
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word {
+        text: String,
+        negated: bool,
+    },
+    Phrase {
+        text: String,
+        negated: bool,
+    },
+    Qualifier {
+        key: String,
+        value: String,
+        negated: bool,
+    },
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+// Parses the query syntax, e.g. `author:alice path:src/llm.rs after:2023-01-01 label:bug "exact
+// phrase" -revert is:merged`:
+// - `author:`, `path:` and `label:` filter by one of several comma-separated values and exclude
+//   them when negated with `-`, e.g. `-author:dependabot`
+// - `after:`, `before:`, `since:` and `until:` take any date that `dates::parse_date` accepts
+// - `is:` takes `merged`, `open`, `closed`, `commit`, `issue` or `pr`, and `kind:` takes `commit`,
+//   `issue` or `pr`
+// - a quoted phrase must occur in the result and `-term` or `-"phrase"` must not; a dash only
+//   negates before a letter or a quote, so "off by -1" and "--verbose" are searched for as is
+// - `AND`, `OR`, `NOT` and parentheses combine terms into a boolean filter on the text
+// Everything else is searched for as is. A query without any of the syntax is returned unfiltered.
+pub fn parse_query_syntax(query: &str) -> ExplicitQuery {
+    let tokens = tokenize(query);
+    // parentheses on their own are part of natural language, e.g. "fix the parser (again)"
+    let has_syntax = tokens.iter().any(|token| {
+        !matches!(
+            token,
+            Token::Word { negated: false, .. } | Token::Open | Token::Close
+        )
+    });
+    if !has_syntax {
+        return ExplicitQuery {
+            parsed_query: ParsedQuery::unfiltered(query.to_string()),
+            ..ExplicitQuery::default()
+        };
+    }
+    let mut explicit_query = ExplicitQuery::default();
+    let mut text_tokens: Vec<Token> = Vec::new();
+    for token in tokens {
+        match token {
+            Token::Qualifier {
+                key,
+                value,
+                negated,
+            } => {
+                explicit_query.has_qualifiers = true;
+                if let Err(e) =
+                    apply_qualifier(&mut explicit_query.parsed_query, &key, &value, negated)
+                {
+                    explicit_query.errors.push(e);
+                }
+            }
+            token => text_tokens.push(token),
+        }
+    }
+    // without operators the words are only ranked, with them they are a filter, e.g. "parser OR
+    // lexer"
+    let has_operators = text_tokens
+        .iter()
+        .any(|token| matches!(token, Token::And | Token::Or | Token::Not));
+    let parsed_query = &mut explicit_query.parsed_query;
+    let mut words: Vec<String> = Vec::new();
+    if let Some(text_query) = Parser::new(text_tokens).parse() {
+        text_query.positive_words(&mut words);
+        let items = match text_query {
+            TextQuery::And(items) => items,
+            text_query => vec![text_query],
+        };
+        let mut required: Vec<TextQuery> = Vec::new();
+        for item in items {
+            match item {
+                // the same as "except ..." in natural language
+                TextQuery::Not(inner) => match *inner {
+                    TextQuery::Term(text) | TextQuery::Phrase(text) => {
+                        parsed_query.excluded_keywords.push(text)
+                    }
+                    inner => required.push(TextQuery::Not(Box::new(inner))),
+                },
+                TextQuery::Term(_) if !has_operators => {}
+                item => required.push(item),
+            }
+        }
+        parsed_query.text_filter = match required.len() {
+            0 => None,
+            1 => required.pop(),
+            _ => Some(TextQuery::And(required)),
+        };
+    }
+    // a query that only consists of filters, e.g. "author:alice is:merged", matches everything
+    // that passes them
+    parsed_query.search_text = if words.is_empty() {
+        query.to_string()
+    } else {
+        words.join(" ")
+    };
+    explicit_query
+}
+
+fn apply_qualifier(
+    parsed_query: &mut ParsedQuery,
+    key: &str,
+    value: &str,
+    negated: bool,
+) -> Result<(), String> {
+    let values: Vec<String> = value
+        .split(',')
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect();
+    if values.is_empty() {
+        return Err(format!("{}: has no value", key));
+    }
+    let (included, excluded) = match key {
+        "author" => (
+            &mut parsed_query.authors,
+            &mut parsed_query.excluded_authors,
+        ),
+        "path" => (&mut parsed_query.paths, &mut parsed_query.excluded_paths),
+        "label" => (&mut parsed_query.labels, &mut parsed_query.excluded_labels),
+        "after" | "before" | "since" | "until" if negated => {
+            return Err(format!("{}: cannot be negated", key));
+        }
+        "after" | "before" | "since" | "until" => {
+            let now = Local::now();
+            let span = parse_date(value, &now)
+                .ok_or_else(|| format!("{}:{} is not a date", key, value))?;
+            let (since, until) = parsed_query.date_range.get_or_insert((None, None));
+            match key {
+                "after" if span.is_instant() => *since = Some(span.end),
+                "after" => *since = Some(span.end + Duration::seconds(1)),
+                "before" if span.is_instant() => *until = Some(span.start),
+                "before" => *until = Some(span.start - Duration::seconds(1)),
+                "since" => *since = Some(span.start),
+                _ => *until = Some(span.end),
+            }
+            return Ok(());
+        }
+        "is" | "kind" if negated => return Err(format!("{}: cannot be negated", key)),
+        "kind" => {
+            for value in values {
+                parsed_query.kind = Some(value.to_lowercase().parse()?);
+            }
+            return Ok(());
+        }
+        _ => {
+            for value in values {
+                match value.to_lowercase().as_str() {
+                    "merged" => {
+                        parsed_query.merged = true;
+                        parsed_query.kind = Some(ResultKind::PullRequest);
+                    }
+                    "pr" => parsed_query.kind = Some(ResultKind::PullRequest),
+                    state_or_kind => {
+                        if let Ok(state) = state_or_kind.parse() {
+                            parsed_query.state = Some(state);
+                        } else if let Ok(kind) = state_or_kind.parse() {
+                            parsed_query.kind = Some(kind);
+                        } else {
+                            return Err(format!(
+                                "is:{} is unknown (expected merged, open, closed, commit, issue or pr)",
+                                value
+                            ));
+                        }
+                    }
+                }
+            }
+            return Ok(());
+        }
+    };
+    if negated {
+        excluded.extend(values);
+    } else {
+        included.extend(values);
+    }
+    Ok(())
+}
+
+// Splits the query into words, quoted phrases and operators. Quotes can also wrap the value of a
+// qualifier, e.g. `label:"good first issue"`.
+fn tokenize(query: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '(' || c == ')' {
+            chars.next();
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            continue;
+        }
+        let mut negated = false;
+        if c == '-' {
+            // otherwise the dash is part of the word, e.g. "-1", "--verbose" or a lone "-"
+            let mut lookahead = chars.clone();
+            lookahead.next();
+            if lookahead
+                .peek()
+                .is_some_and(|next| next.is_alphabetic() || *next == '"')
+            {
+                chars.next();
+                negated = true;
+            }
+        }
+        let mut text = String::new();
+        let mut is_quoted = false;
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '(' || c == ')' {
+                break;
+            }
+            chars.next();
+            if c == '"' {
+                // an unterminated quote runs until the end of the query
+                is_quoted = true;
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    text.push(c);
+                }
+            } else {
+                text.push(c);
+            }
+        }
+        let qualifier = text.split_once(':').and_then(|(key, value)| {
+            let key = key.to_lowercase();
+            QUALIFIERS
+                .contains(&key.as_str())
+                .then(|| (key, value.to_string()))
+        });
+        tokens.push(match qualifier {
+            Some((key, value)) => Token::Qualifier {
+                key,
+                value,
+                negated,
+            },
+            None if is_quoted => Token::Phrase { text, negated },
+            None if negated => word_token(text, true),
+            None => match text.as_str() {
+                "AND" => Token::And,
+                "OR" => Token::Or,
+                "NOT" => Token::Not,
+                _ => word_token(text, false),
+            },
+        });
+    }
+    tokens
+}
+
+fn word_token(text: String, negated: bool) -> Token {
+    Token::Word { text, negated }
+}
+
+// A recursive descent parser of the text tokens with the usual precedence: NOT binds tighter than
+// AND, which binds tighter than OR, and adjacent terms are joined with AND. Operators without
+// operands and unbalanced parentheses are skipped rather than rejected.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Parser {
+        Parser {
+            tokens,
+            position: 0,
+        }
+    }
+
+    fn parse(mut self) -> Option<TextQuery> {
+        let mut queries = Vec::new();
+        while self.position < self.tokens.len() {
+            match self.parse_or() {
+                Some(query) => queries.push(query),
+                // e.g. a closing parenthesis without an opening one
+                None => self.position += 1,
+            }
+        }
+        join(queries, TextQuery::And)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn parse_or(&mut self) -> Option<TextQuery> {
+        let mut queries: Vec<TextQuery> = self.parse_and().into_iter().collect();
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            queries.extend(self.parse_and());
+        }
+        join(queries, TextQuery::Or)
+    }
+
+    fn parse_and(&mut self) -> Option<TextQuery> {
+        let mut queries: Vec<TextQuery> = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some(Token::Or) | Some(Token::Close) => break,
+                Some(Token::And) => self.position += 1,
+                Some(_) => queries.extend(self.parse_unary()),
+            }
+        }
+        join(queries, TextQuery::And)
+    }
+
+    fn parse_unary(&mut self) -> Option<TextQuery> {
+        let token = self.peek()?.clone();
+        self.position += 1;
+        match token {
+            Token::Not => self
+                .parse_unary()
+                .map(|query| TextQuery::Not(Box::new(query))),
+            Token::Word { text, negated } => Some(negate(TextQuery::Term(text), negated)),
+            Token::Phrase { text, negated } => Some(negate(TextQuery::Phrase(text), negated)),
+            Token::Open => {
+                let query = self.parse_or();
+                if self.peek() == Some(&Token::Close) {
+                    self.position += 1;
+                }
+                query
+            }
+            Token::Qualifier { .. } | Token::And | Token::Or | Token::Close => None,
+        }
+    }
+}
+
+fn negate(query: TextQuery, negated: bool) -> TextQuery {
+    if negated {
+        TextQuery::Not(Box::new(query))
+    } else {
+        query
+    }
+}
+
+fn join(
+    mut queries: Vec<TextQuery>,
+    combine: fn(Vec<TextQuery>) -> TextQuery,
+) -> Option<TextQuery> {
+    match queries.len() {
+        0 => None,
+        1 => queries.pop(),
+        _ => Some(combine(queries)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::github::IssueState;
+
+    fn term(text: &str) -> TextQuery {
+        TextQuery::Term(text.to_string())
+    }
+
+    fn phrase(text: &str) -> TextQuery {
+        TextQuery::Phrase(text.to_string())
+    }
+
+    fn not(query: TextQuery) -> TextQuery {
+        TextQuery::Not(Box::new(query))
+    }
+
+    fn text_filter(query: &str) -> Option<TextQuery> {
+        parse_query_syntax(query).parsed_query.text_filter
+    }
+
+    fn assert_passthrough(query: &str) {
+        let explicit_query = parse_query_syntax(query);
+        let parsed_query = &explicit_query.parsed_query;
+        assert!(!explicit_query.has_qualifiers, "{}", query);
+        assert!(explicit_query.errors.is_empty(), "{}", query);
+        assert_eq!(parsed_query.search_text, query);
+        assert_eq!(parsed_query.text_filter, None, "{}", query);
+        assert!(parsed_query.excluded_keywords.is_empty(), "{}", query);
+        assert!(!parsed_query.has_filters(), "{}", query);
+    }
+
+    #[test]
+    fn test_plain_text_passthrough() {
+        assert_passthrough("why does the parser crash on empty input");
+        assert_passthrough("fix the parser (again)");
+        assert_passthrough("std::io errors from http://example.com");
+        assert_passthrough("rock and roll or not");
+    }
+
+    #[test]
+    fn test_dash_without_letter_or_quote_is_not_negation() {
+        assert_passthrough("off by -1 error");
+        assert_passthrough("--verbose flag is ignored");
+        assert_passthrough("a - b overflows");
+        assert_passthrough("-");
+        let parsed_query = parse_query_syntax("off by -1 author:alice").parsed_query;
+        assert!(parsed_query.excluded_keywords.is_empty());
+        assert_eq!(parsed_query.search_text, "off by -1");
+        assert_eq!(parsed_query.authors, vec!["alice"]);
+    }
+
+    #[test]
+    fn test_negation() {
+        let explicit_query = parse_query_syntax(
+            r#"parser -revert -"work in progress" -author:dependabot -label:wontfix,duplicate"#,
+        );
+        let parsed_query = &explicit_query.parsed_query;
+        assert!(explicit_query.errors.is_empty());
+        assert_eq!(
+            parsed_query.excluded_keywords,
+            vec!["revert", "work in progress"]
+        );
+        assert_eq!(parsed_query.excluded_authors, vec!["dependabot"]);
+        assert_eq!(parsed_query.excluded_labels, vec!["wontfix", "duplicate"]);
+        assert_eq!(parsed_query.text_filter, None);
+        assert_eq!(parsed_query.search_text, "parser");
+        // a negated word is syntax even without qualifiers
+        let explicit_query = parse_query_syntax("parser -lexer");
+        assert!(!explicit_query.has_qualifiers);
+        assert_eq!(explicit_query.parsed_query.excluded_keywords, vec!["lexer"]);
+        assert_eq!(explicit_query.parsed_query.search_text, "parser");
+    }
+
+    #[test]
+    fn test_negated_dates_and_states_are_errors() {
+        let explicit_query = parse_query_syntax("-after:2023-01-01 -is:open parser");
+        assert_eq!(
+            explicit_query.errors,
+            vec!["after: cannot be negated", "is: cannot be negated"]
+        );
+        assert!(explicit_query.parsed_query.date_range.is_none());
+        assert!(explicit_query.parsed_query.state.is_none());
+    }
+
+    #[test]
+    fn test_qualifiers() {
+        let explicit_query = parse_query_syntax(
+            "tokenizer author:alice,bob path:src/ label:bug is:open after:2023-01-01",
+        );
+        let parsed_query = &explicit_query.parsed_query;
+        assert!(explicit_query.has_qualifiers);
+        assert!(explicit_query.errors.is_empty());
+        assert_eq!(parsed_query.authors, vec!["alice", "bob"]);
+        assert_eq!(parsed_query.paths, vec!["src/"]);
+        assert_eq!(parsed_query.labels, vec!["bug"]);
+        assert_eq!(parsed_query.state, Some(IssueState::Open));
+        assert!(parsed_query
+            .date_range
+            .is_some_and(|(since, until)| since.is_some() && until.is_none()));
+        assert_eq!(parsed_query.search_text, "tokenizer");
+        let parsed_query = parse_query_syntax("crash kind:issue").parsed_query;
+        assert_eq!(parsed_query.kind, Some(ResultKind::Issue));
+        assert_eq!(parsed_query.search_text, "crash");
+        let parsed_query = parse_query_syntax("is:merged").parsed_query;
+        assert!(parsed_query.merged);
+        assert_eq!(parsed_query.kind, Some(ResultKind::PullRequest));
+        // a query of only filters searches for itself
+        assert_eq!(parsed_query.search_text, "is:merged");
+    }
+
+    #[test]
+    fn test_invalid_qualifier_values() {
+        let explicit_query = parse_query_syntax("author: is:bogus before:someday parser");
+        assert_eq!(explicit_query.errors.len(), 3);
+        assert!(explicit_query.errors[0].contains("has no value"));
+        assert!(explicit_query.errors[1].contains("is:bogus is unknown"));
+        assert!(explicit_query.errors[2].contains("not a date"));
+        assert_eq!(explicit_query.parsed_query.search_text, "parser");
+    }
+
+    #[test]
+    fn test_quoted_qualifier_values() {
+        let parsed_query = parse_query_syntax(
+            r#"label:"good first issue" -author:"Jane Doe" path:"docs/user guide.md",src/"#,
+        )
+        .parsed_query;
+        assert_eq!(parsed_query.labels, vec!["good first issue"]);
+        assert_eq!(parsed_query.excluded_authors, vec!["Jane Doe"]);
+        assert_eq!(parsed_query.paths, vec!["docs/user guide.md", "src/"]);
+        assert_eq!(parsed_query.text_filter, None);
+    }
+
+    #[test]
+    fn test_unterminated_quotes() {
+        // the quote runs until the end of the query
+        let parsed_query = parse_query_syntax(r#"fix "parser bug in lexer"#).parsed_query;
+        assert_eq!(
+            parsed_query.text_filter,
+            Some(phrase("parser bug in lexer"))
+        );
+        assert_eq!(parsed_query.search_text, "fix parser bug in lexer");
+        let parsed_query = parse_query_syntax(r#"label:"good first"#).parsed_query;
+        assert_eq!(parsed_query.labels, vec!["good first"]);
+        let parsed_query = parse_query_syntax(r#"crash -"flaky test"#).parsed_query;
+        assert_eq!(parsed_query.excluded_keywords, vec!["flaky test"]);
+    }
+
+    #[test]
+    fn test_phrases() {
+        let parsed_query = parse_query_syntax(
+            r#"crash "Out  of
+            Memory""#,
+        )
+        .parsed_query;
+        let text_filter = parsed_query.text_filter.unwrap();
+        assert!(text_filter.matches(&normalize_text("The process ran out of memory.")));
+        assert!(!text_filter.matches(&normalize_text("out of disk memory")));
+    }
+
+    #[test]
+    fn test_precedence() {
+        // NOT binds tighter than AND, which binds tighter than OR
+        assert_eq!(
+            text_filter("a AND NOT b OR c"),
+            Some(TextQuery::Or(vec![
+                TextQuery::And(vec![term("a"), not(term("b"))]),
+                term("c"),
+            ]))
+        );
+        assert_eq!(
+            text_filter("a OR b AND c"),
+            Some(TextQuery::Or(vec![
+                term("a"),
+                TextQuery::And(vec![term("b"), term("c")]),
+            ]))
+        );
+        // adjacent terms are joined with AND
+        assert_eq!(
+            text_filter("a OR b c"),
+            Some(TextQuery::Or(vec![
+                term("a"),
+                TextQuery::And(vec![term("b"), term("c")]),
+            ]))
+        );
+        assert_eq!(
+            text_filter("(a OR b) c"),
+            Some(TextQuery::And(vec![
+                TextQuery::Or(vec![term("a"), term("b")]),
+                term("c"),
+            ]))
+        );
+        assert_eq!(
+            text_filter("parser NOT (lexer OR tokenizer)"),
+            Some(TextQuery::And(vec![
+                term("parser"),
+                not(TextQuery::Or(vec![term("lexer"), term("tokenizer")])),
+            ]))
+        );
+        // NOT of a single term is an excluded keyword
+        let parsed_query = parse_query_syntax("parser AND NOT lexer").parsed_query;
+        assert_eq!(parsed_query.excluded_keywords, vec!["lexer"]);
+        assert_eq!(parsed_query.text_filter, Some(term("parser")));
+        assert_eq!(parsed_query.search_text, "parser");
+    }
+
+    #[test]
+    fn test_malformed_operators_are_skipped() {
+        assert_eq!(text_filter("OR a"), Some(term("a")));
+        assert_eq!(text_filter("a AND"), Some(term("a")));
+        assert_eq!(
+            text_filter("(a OR b"),
+            Some(TextQuery::Or(vec![term("a"), term("b")]))
+        );
+        assert_eq!(
+            text_filter("a OR b) c"),
+            Some(TextQuery::And(vec![
+                TextQuery::Or(vec![term("a"), term("b")]),
+                term("c"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_text_query_matches() {
+        let query = TextQuery::And(vec![
+            term("Parsers"),
+            not(TextQuery::Or(vec![
+                term("lexer"),
+                phrase("Work In Progress"),
+            ])),
+        ]);
+        assert!(query.matches("fix the parser"));
+        assert!(!query.matches("fix the parser and the lexer"));
+        assert!(!query.matches("parser: work in progress"));
+        assert!(!query.matches("fix the tokenizer"));
+    }
+}
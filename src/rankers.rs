@@ -1,16 +1,14 @@
+use crate::error::GitmError;
 use std::fmt::Display;
 
 pub trait Ranker<T, U> {
-    fn rank(
-        &self,
-        query: T,
-        corpus: Vec<U>,
-    ) -> Result<Vec<RankingResult<U>>, Box<dyn std::error::Error>>
+    fn rank(&self, query: T, corpus: Vec<U>) -> Result<Vec<RankingResult<U>>, GitmError>
     where
         T: Display + Clone,
         U: Display + Clone;
 }
 
+#[derive(Clone)]
 pub struct RankingResult<T>
 where
     T: Display + Clone,
@@ -24,7 +22,7 @@ where
     T: Display + Clone,
 {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.score.partial_cmp(&other.score)
+        Some(self.cmp(other))
     }
 }
 
@@ -44,6 +42,6 @@ where
     T: Display + Clone,
 {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+        self.score.total_cmp(&other.score)
     }
 }
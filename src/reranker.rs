@@ -0,0 +1,265 @@
+use crate::classifier::LLMBinaryClassifierContext;
+use crate::llm::{ChatError, ChatModel, Property};
+use crate::rankers::RankingResult;
+use crate::store::Identifiable;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Display;
+
+pub const DEFAULT_RERANK_TOP_N: usize = 50;
+// keeps the prompt small enough for 50 candidates while still showing most commit messages whole
+const MAX_CANDIDATE_SUMMARY_CHARS: usize = 600;
+
+pub struct LLMReranker {
+    model: ChatModel,
+}
+
+impl LLMReranker {
+    pub fn new(model: ChatModel) -> Self {
+        Self { model }
+    }
+
+    // Asks the model to order the candidates by relevance to the query. Candidates that the model
+    // leaves out keep their original relative order after the ones it ranked. The returned scores
+    // are rank-based (the best candidate scores 1.0) since the model does not produce scores.
+    pub async fn rerank<U>(
+        &self,
+        query: String,
+        candidates: Vec<RankingResult<U>>,
+    ) -> Result<Vec<RankingResult<U>>, ChatError>
+    where
+        U: Display + Clone + Identifiable,
+    {
+        if candidates.len() < 2 {
+            return Ok(candidates);
+        }
+        let instruction = String::from(
+            "Determine if any of the candidates are relevant to the user's query. If so, order the candidates from most to least relevant to the query.",
+        );
+        let result_property = (
+            String::from("ranking"),
+            Property {
+                r#type: String::from("string"),
+                description: String::from(
+                    "The comma-separated numbers of the relevant candidates, from most to least relevant (e.g. \"3,1,7\")",
+                ),
+            },
+        );
+        let raw_classifier = LLMBinaryClassifierContext::builder(self.model.clone(), instruction)
+            .result_property(result_property)
+            .additional_information(format!(
+                "## Candidates\n{}",
+                candidates
+                    .iter()
+                    .enumerate()
+                    .map(|(i, candidate)| format!(
+                        "[{}] {}\n{}",
+                        i + 1,
+                        candidate.item.id(),
+                        summarize(candidate.item.to_string().as_str())
+                    ))
+                    .collect::<Vec<String>>()
+                    .join("\n\n")
+            ))
+            .build();
+        let tool_call = raw_classifier.raw_classification(query).await?;
+        #[derive(Debug, Serialize, Deserialize)]
+        struct RawResult {
+            classification: bool,
+            ranking: Option<String>,
+        }
+        let result = serde_json::from_str::<RawResult>(tool_call.function.arguments.as_str())?;
+        match (result.classification, result.ranking) {
+            (true, Some(ranking)) => {
+                let ranking = parse_ranking(ranking.as_str(), candidates.len());
+                Ok(reorder(candidates, ranking))
+            }
+            _ => Ok(candidates),
+        }
+    }
+}
+
+// Puts the candidates at the indices of `ranking` first and the others after them, in their
+// original order, with rank-based scores.
+fn reorder<U>(candidates: Vec<RankingResult<U>>, ranking: Vec<usize>) -> Vec<RankingResult<U>>
+where
+    U: Display + Clone,
+{
+    let num_candidates = candidates.len();
+    let mut candidates: Vec<Option<RankingResult<U>>> = candidates.into_iter().map(Some).collect();
+    let mut reranked = Vec::with_capacity(num_candidates);
+    for i in ranking.into_iter().chain(0..num_candidates) {
+        if let Some(candidate) = candidates.get_mut(i).and_then(Option::take) {
+            reranked.push(RankingResult {
+                score: (num_candidates - reranked.len()) as f64 / num_candidates as f64,
+                item: candidate.item,
+            });
+        }
+    }
+    reranked
+}
+
+// Parses the 1-based candidate numbers into 0-based indices, dropping anything out of range and
+// duplicates.
+fn parse_ranking(ranking: &str, num_candidates: usize) -> Vec<usize> {
+    let mut seen = HashSet::new();
+    ranking
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|s| s.parse::<usize>().ok())
+        .filter(|n| *n >= 1 && *n <= num_candidates)
+        .map(|n| n - 1)
+        .filter(|i| seen.insert(*i))
+        .collect()
+}
+
+fn summarize(s: &str) -> String {
+    let s = s.trim();
+    if s.chars().count() <= MAX_CANDIDATE_SUMMARY_CHARS {
+        return s.to_string();
+    }
+    format!(
+        "{}...",
+        s.chars()
+            .take(MAX_CANDIDATE_SUMMARY_CHARS)
+            .collect::<String>()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{HttpClient, RetryConfig};
+    use crate::mock_server::{MockResponse, MockServer};
+    use crate::providers::OpenAIProvider;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Item(&'static str);
+
+    impl Display for Item {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl Identifiable for Item {
+        fn id(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    fn candidates(ids: &[&'static str]) -> Vec<RankingResult<Item>> {
+        ids.iter()
+            .enumerate()
+            .map(|(i, id)| RankingResult {
+                score: 10.0 - i as f64,
+                item: Item(id),
+            })
+            .collect()
+    }
+
+    fn ids(results: &[RankingResult<Item>]) -> Vec<&'static str> {
+        results.iter().map(|result| result.item.0).collect()
+    }
+
+    #[test]
+    fn test_parse_ranking() {
+        assert_eq!(parse_ranking("3,1,2", 3), vec![2, 0, 1]);
+        assert_eq!(parse_ranking(" 2 , 1 ", 3), vec![1, 0]);
+        // out of range
+        assert_eq!(parse_ranking("0,4,2,10", 3), vec![1]);
+        // duplicates keep their first position
+        assert_eq!(parse_ranking("2,1,2,1,3", 3), vec![1, 0, 2]);
+        // other separators and junk around the numbers
+        assert_eq!(parse_ranking("[3] then [1]; candidate two", 3), vec![2, 0]);
+        assert_eq!(parse_ranking("3\n1", 3), vec![2, 0]);
+        assert_eq!(parse_ranking("99999999999999999999999,1", 3), vec![0]);
+        assert!(parse_ranking("", 3).is_empty());
+        assert!(parse_ranking("none of them", 3).is_empty());
+        assert!(parse_ranking("1,2", 0).is_empty());
+    }
+
+    #[test]
+    fn test_reorder() {
+        let reranked = reorder(candidates(&["a", "b", "c", "d"]), vec![2, 0]);
+        // the candidates that were not ranked keep their order after the ranked ones
+        assert_eq!(ids(&reranked), vec!["c", "a", "b", "d"]);
+        let scores: Vec<f64> = reranked.iter().map(|result| result.score).collect();
+        assert_eq!(scores, vec![1.0, 0.75, 0.5, 0.25]);
+    }
+
+    #[test]
+    fn test_reorder_without_ranking() {
+        let reranked = reorder(candidates(&["a", "b", "c"]), Vec::new());
+        assert_eq!(ids(&reranked), vec!["a", "b", "c"]);
+        // out of range indices are ignored
+        let reranked = reorder(candidates(&["a", "b"]), vec![5, 1]);
+        assert_eq!(ids(&reranked), vec!["b", "a"]);
+        assert!(reorder(candidates(&[]), vec![0]).is_empty());
+    }
+
+    fn tool_call_response(arguments: &str) -> MockResponse {
+        let body = serde_json::json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "binary_classification", "arguments": arguments},
+                    }],
+                },
+            }],
+            "usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2},
+        });
+        MockResponse::new(200, body.to_string().as_str())
+    }
+
+    fn reranker(server: &MockServer) -> LLMReranker {
+        let provider = OpenAIProvider::new(
+            String::from("key"),
+            String::from("gpt-4"),
+            HttpClient::new(RetryConfig::default()).unwrap(),
+        )
+        .base_url(server.url().to_string());
+        LLMReranker::new(ChatModel::from(provider))
+    }
+
+    #[tokio::test]
+    async fn test_rerank() {
+        let server = MockServer::start(vec![tool_call_response(
+            r#"{"classification": true, "ranking": "3, 3, 9, 1"}"#,
+        )])
+        .await;
+        let reranked = reranker(&server)
+            .rerank(String::from("parser"), candidates(&["a", "b", "c"]))
+            .await
+            .unwrap();
+        assert_eq!(ids(&reranked), vec!["c", "a", "b"]);
+        assert_eq!(server.num_requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rerank_keeps_order_when_nothing_is_relevant() {
+        let server =
+            MockServer::start(vec![tool_call_response(r#"{"classification": false}"#)]).await;
+        let reranked = reranker(&server)
+            .rerank(String::from("parser"), candidates(&["a", "b", "c"]))
+            .await
+            .unwrap();
+        assert_eq!(ids(&reranked), vec!["a", "b", "c"]);
+        // the scores of the earlier ranking are kept
+        assert_eq!(reranked[0].score, 10.0);
+    }
+
+    #[tokio::test]
+    async fn test_rerank_single_candidate_needs_no_model() {
+        let server = MockServer::start(Vec::new()).await;
+        let reranked = reranker(&server)
+            .rerank(String::from("parser"), candidates(&["a"]))
+            .await
+            .unwrap();
+        assert_eq!(ids(&reranked), vec!["a"]);
+        assert_eq!(server.num_requests(), 0);
+    }
+}
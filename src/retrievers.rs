@@ -1,15 +1,18 @@
+use crate::error::GitmError;
+use crate::rankers::RankingResult;
 use crate::store::Store;
 use std::fmt::Display;
+use std::future::Future;
 
 pub trait Retriever<T, U>
 where
-    T: Display + Clone,
-    U: Display + Clone,
+    T: Display + Clone + Send,
+    U: Display + Clone + Send + Sync,
 {
     fn retrieve(
         &self,
         query: T,
-        store: Store<U>,
+        store: &Store<U>,
         max_num_results: usize,
-    ) -> Result<Vec<U>, Box<dyn std::error::Error>>;
+    ) -> impl Future<Output = Result<Vec<RankingResult<U>>, GitmError>> + Send;
 }
@@ -1,22 +1,62 @@
-use crate::bm25::{BM25Ranker, BM25Retriever};
-use crate::classifier::{BinaryClassificationResult, BinaryClassifier};
+use crate::authors::{AuthorResolver, Identity, Mailmap};
+use crate::bm25::{BM25FRanker, FieldBoosts, FieldedIndex};
+use crate::document::{DocumentStats, Fielded};
+use crate::embeddings::{
+    EmbeddingBackend, EmbeddingCache, EmbeddingRetriever, HashingEmbeddingBackend,
+};
+use crate::error::GitmError;
+use crate::fusion::{fuse, FusionConfig};
 use crate::git;
-use crate::git::{Author, Commit, FilterConfig};
+use crate::git::{Commit, FilterConfig};
 use crate::github;
-use crate::github::Issue;
-use crate::llm::ChatModel;
-use crate::mention_classifiers::{AuthorMentionBinaryClassifier, DateTimeMentionClassifier};
+use crate::github::{Issue, IssueFilterConfig, PullRequest};
+use crate::index::{index_dir, CommitIndex};
+use crate::llm::{ChatError, ChatModel};
+use crate::mention_classifiers::{
+    RuleBasedAuthorMentionClassifier, RuleBasedDateTimeMentionClassifier,
+    RuleBasedPathMentionClassifier,
+};
+use crate::paths::resolve_path_mention;
+use crate::query_parser::{LLMQueryParser, ParsedQuery, QueryParser, RuleBasedQueryParser};
+use crate::query_syntax::parse_query_syntax;
+use crate::rankers::RankingResult;
+use crate::reranker::{LLMReranker, DEFAULT_RERANK_TOP_N};
 use crate::retrievers::Retriever;
-use crate::splitters::PuncSplitter;
-use crate::store::Store;
-use chrono::{DateTime, Utc};
-use std::collections::HashSet;
+use crate::search_result::{ResultKind, SearchResult};
+use crate::store::{Identifiable, Store};
+use crate::utils::run_blocking;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Each pass retrieves more candidates than requested so that items ranked moderately well by
+// several passes can still make it into the fused results.
+const FUSION_CANDIDATES_MULTIPLIER: usize = 5;
 
 pub struct SearchAgent {
+    // None in offline mode
+    model: Option<ChatModel>,
     git_client: git::Client,
     github_client: github::Client,
-    author_mention_classifier: AuthorMentionBinaryClassifier,
-    datetime_mention_classifier: DateTimeMentionClassifier,
+    author_resolver: AuthorResolver,
+    query_parser: QueryParsers,
+    reranker: Option<LLMReranker>,
+}
+
+// The results of a search and the failures that it recovered from, e.g. a reranking that was
+// skipped because the LLM was unavailable.
+pub struct SearchResponse {
+    pub results: Vec<RankingResult<SearchResult>>,
+    pub warnings: Vec<GitmError>,
+    // how long each stage of the search took and, last, the total. Some of the stages run at the
+    // same time, so they add up to more than the total.
+    pub timings: Vec<(&'static str, Duration)>,
+}
+
+enum QueryParsers {
+    Model(Box<LLMQueryParser>),
+    RuleBased(Box<RuleBasedQueryParser>),
 }
 
 pub struct SearchConfig {
@@ -24,9 +64,19 @@ pub struct SearchConfig {
     max_num_results: usize,
     include_commits: bool,
     include_issues: bool,
+    include_pull_requests: bool,
     include_code_patches: bool,
+    include_semantic: bool,
     disable_classifications: bool,
     search_all: bool,
+    rebuild_index: bool,
+    fusion: FusionConfig,
+    field_boosts: FieldBoosts,
+    rerank: bool,
+    rerank_top_n: usize,
+    issue_filter: IssueFilterConfig,
+    paths: Vec<String>,
+    excluded_paths: Vec<String>,
 }
 
 pub struct SearchConfigBuilder {
@@ -34,9 +84,19 @@ pub struct SearchConfigBuilder {
     max_num_results: usize,
     include_commits: bool,
     include_issues: bool,
+    include_pull_requests: bool,
     include_code_patches: bool,
+    include_semantic: bool,
     disable_classifications: bool,
     search_all: bool,
+    rebuild_index: bool,
+    fusion: FusionConfig,
+    field_boosts: FieldBoosts,
+    rerank: bool,
+    rerank_top_n: usize,
+    issue_filter: IssueFilterConfig,
+    paths: Vec<String>,
+    excluded_paths: Vec<String>,
 }
 
 impl SearchConfigBuilder {
@@ -46,9 +106,19 @@ impl SearchConfigBuilder {
             max_num_results: 10,
             include_commits: true,
             include_issues: false,
+            include_pull_requests: false,
             include_code_patches: false,
+            include_semantic: false,
             disable_classifications: false,
             search_all: false,
+            rebuild_index: false,
+            fusion: FusionConfig::default(),
+            field_boosts: FieldBoosts::default(),
+            rerank: false,
+            rerank_top_n: DEFAULT_RERANK_TOP_N,
+            issue_filter: IssueFilterConfig::default(),
+            paths: Vec::new(),
+            excluded_paths: Vec::new(),
         }
     }
 
@@ -67,11 +137,21 @@ impl SearchConfigBuilder {
         self
     }
 
+    pub fn include_pull_requests(mut self, include_pull_requests: bool) -> SearchConfigBuilder {
+        self.include_pull_requests = include_pull_requests;
+        self
+    }
+
     pub fn include_code_patches(mut self, include_code_patches: bool) -> SearchConfigBuilder {
         self.include_code_patches = include_code_patches;
         self
     }
 
+    pub fn include_semantic(mut self, include_semantic: bool) -> SearchConfigBuilder {
+        self.include_semantic = include_semantic;
+        self
+    }
+
     pub fn disable_classifications(mut self, disable_classifications: bool) -> SearchConfigBuilder {
         self.disable_classifications = disable_classifications;
         self
@@ -82,129 +162,606 @@ impl SearchConfigBuilder {
         self
     }
 
+    pub fn rebuild_index(mut self, rebuild_index: bool) -> SearchConfigBuilder {
+        self.rebuild_index = rebuild_index;
+        self
+    }
+
+    pub fn fusion(mut self, fusion: FusionConfig) -> SearchConfigBuilder {
+        self.fusion = fusion;
+        self
+    }
+
+    // How much matches in the title, body, code and paths count. The code only counts with
+    // `include_code_patches`.
+    pub fn field_boosts(mut self, field_boosts: FieldBoosts) -> SearchConfigBuilder {
+        self.field_boosts = field_boosts;
+        self
+    }
+
+    pub fn rerank(mut self, rerank: bool) -> SearchConfigBuilder {
+        self.rerank = rerank;
+        self
+    }
+
+    pub fn rerank_top_n(mut self, rerank_top_n: usize) -> SearchConfigBuilder {
+        self.rerank_top_n = rerank_top_n;
+        self
+    }
+
+    pub fn issue_filter(mut self, issue_filter: IssueFilterConfig) -> SearchConfigBuilder {
+        self.issue_filter = issue_filter;
+        self
+    }
+
+    // Only search commits that touch one of these paths, see `paths::path_matches`.
+    pub fn paths(mut self, paths: Vec<String>) -> SearchConfigBuilder {
+        self.paths = paths;
+        self
+    }
+
+    // Leave out the commits that only touch these paths.
+    pub fn excluded_paths(mut self, excluded_paths: Vec<String>) -> SearchConfigBuilder {
+        self.excluded_paths = excluded_paths;
+        self
+    }
+
     pub fn build(self) -> SearchConfig {
         SearchConfig {
             query: self.query,
             max_num_results: self.max_num_results,
             include_commits: self.include_commits,
             include_issues: self.include_issues,
+            include_pull_requests: self.include_pull_requests,
             include_code_patches: self.include_code_patches,
+            include_semantic: self.include_semantic,
             disable_classifications: self.disable_classifications,
             search_all: self.search_all,
+            rebuild_index: self.rebuild_index,
+            fusion: self.fusion,
+            field_boosts: self.field_boosts,
+            rerank: self.rerank,
+            rerank_top_n: self.rerank_top_n,
+            issue_filter: self.issue_filter,
+            paths: self.paths,
+            excluded_paths: self.excluded_paths,
+        }
+    }
+}
+
+impl SearchConfig {
+    // the reranker needs a deeper candidate list than the number of results that are shown
+    fn num_fused_results(&self) -> usize {
+        if self.rerank {
+            self.max_num_results.max(self.rerank_top_n)
+        } else {
+            self.max_num_results
         }
     }
 }
 
 impl SearchAgent {
-    pub fn new(model: ChatModel) -> SearchAgent {
+    pub fn new(model: ChatModel) -> Result<SearchAgent, GitmError> {
         let git_client = git::Client::new();
-        let github_client = github::Client::new();
-        let all_authors = git_client.get_all_authors().unwrap().into_iter().collect();
-        let author_mention_classifier =
-            AuthorMentionBinaryClassifier::new(model.clone(), all_authors);
-        let datetime_mention_classifier = DateTimeMentionClassifier::new(model.clone());
-        SearchAgent {
+        let github_client = github::Client::new()?;
+        let author_resolver = git_author_resolver(&git_client)?;
+        let query_parser = QueryParsers::Model(Box::new(LLMQueryParser::new(
+            model.clone(),
+            author_resolver.identities(),
+        )));
+        let reranker = LLMReranker::new(model.clone());
+        Ok(SearchAgent {
+            model: Some(model),
             git_client,
             github_client,
-            author_mention_classifier,
-            datetime_mention_classifier,
-        }
+            author_resolver,
+            query_parser,
+            reranker: Some(reranker),
+        })
     }
 
-    pub async fn search(
-        &self,
-        search_config: SearchConfig,
-    ) -> Result<(Vec<Commit>, Vec<Issue>), Box<dyn std::error::Error>> {
-        // TODO: add concurrency
-        let mut commit_results: Vec<Commit> = Vec::new();
-        let mut issue_results: Vec<Issue> = Vec::new();
-        if search_config.include_commits {
-            let mut filter_config: Option<FilterConfig> = None;
-            if !search_config.disable_classifications {
-                let author_classification_result: BinaryClassificationResult<Author> = self
-                    .author_mention_classifier
-                    .classify(search_config.query.clone())
-                    .await
-                    .unwrap();
-                let datetime_classification_result: BinaryClassificationResult<(
-                    Option<DateTime<Utc>>,
-                    Option<DateTime<Utc>>,
-                )> = self
-                    .datetime_mention_classifier
-                    .classify(search_config.query.clone())
-                    .await
-                    .unwrap();
-                let mut filter = FilterConfig {
-                    author: None,
-                    date_range: None,
-                    git_log_get_all: search_config.search_all,
-                };
-                if author_classification_result.classification {
-                    if let Some(author) = author_classification_result.content {
-                        filter.author = Some(author);
+    // An agent that never calls an LLM: filters are extracted from the query with rules, there is
+    // no reranking and the semantic pass uses local hashed embeddings.
+    pub fn offline() -> Result<SearchAgent, GitmError> {
+        let git_client = git::Client::new();
+        let github_client = github::Client::new()?;
+        let author_resolver = git_author_resolver(&git_client)?;
+        let all_tags = git_client.get_all_tags().unwrap_or_default();
+        let query_parser = QueryParsers::RuleBased(Box::new(RuleBasedQueryParser::new(
+            RuleBasedAuthorMentionClassifier::new(author_resolver.clone()),
+            RuleBasedDateTimeMentionClassifier::new(all_tags),
+            RuleBasedPathMentionClassifier::new(git_client.get_all_files().unwrap_or_default()),
+        )));
+        Ok(SearchAgent {
+            model: None,
+            git_client,
+            github_client,
+            author_resolver,
+            query_parser,
+            reranker: None,
+        })
+    }
+
+    pub fn github_client(mut self, github_client: github::Client) -> SearchAgent {
+        self.github_client = github_client;
+        self
+    }
+
+    pub async fn search(&self, search_config: SearchConfig) -> Result<SearchResponse, GitmError> {
+        let search_start = Instant::now();
+        let mut timings: Vec<(&'static str, Duration)> = Vec::new();
+        let mut warnings: Vec<GitmError> = Vec::new();
+        // parsing the query, updating the commit index and fetching from GitHub do not depend on
+        // each other, so they run at the same time even though the kind of result in the query
+        // can make the commits or the GitHub results unnecessary
+        let (parsed_query, commit_index, github_results) = tokio::join!(
+            timed(self.parse_query(&search_config)),
+            timed(async {
+                if !search_config.include_commits {
+                    return None;
+                }
+                Some(self.update_commit_index(search_config.rebuild_index).await)
+            }),
+            timed(async {
+                if !search_config.include_issues && !search_config.include_pull_requests {
+                    return None;
+                }
+                Some(
+                    self.get_github_results(
+                        search_config.include_issues,
+                        search_config.include_pull_requests,
+                    )
+                    .await,
+                )
+            }),
+        );
+        let ((parsed_query, parse_errors), parse_time) = parsed_query;
+        let (commit_index, index_time) = commit_index;
+        let (github_results, github_time) = github_results;
+        warnings.extend(parse_errors);
+        timings.push(("parse query", parse_time));
+        // a query for one kind of result narrows the search, e.g. "open issues about the parser",
+        // but only to one of the kinds that are searched
+        let is_kind_searched = match parsed_query.kind {
+            Some(ResultKind::Commit) => search_config.include_commits,
+            Some(ResultKind::Issue) => search_config.include_issues,
+            Some(ResultKind::PullRequest) => search_config.include_pull_requests,
+            None => false,
+        };
+        let only = |kind: ResultKind| !is_kind_searched || parsed_query.kind == Some(kind);
+        let include_commits = search_config.include_commits && only(ResultKind::Commit);
+        let include_issues = search_config.include_issues && only(ResultKind::Issue);
+        let include_pull_requests =
+            search_config.include_pull_requests && only(ResultKind::PullRequest);
+        let mut all_github_issues: Vec<Issue> = Vec::new();
+        let mut all_github_pull_requests: Vec<PullRequest> = Vec::new();
+        if let (true, Some(github_results)) =
+            (include_issues || include_pull_requests, github_results)
+        {
+            timings.push(("fetch GitHub", github_time));
+            match github_results {
+                Ok((issues, pull_requests)) => {
+                    if include_issues {
+                        all_github_issues = issues;
+                    }
+                    if include_pull_requests {
+                        all_github_pull_requests = pull_requests;
                     }
                 }
-                if datetime_classification_result.classification {
-                    if let Some((start_date, end_date)) = datetime_classification_result.content {
-                        filter.date_range = Some((start_date, end_date));
+                // the commits can still be searched without GitHub
+                Err(e) if include_commits => warnings.push(e),
+                Err(e) => return Err(e),
+            }
+        }
+        let commit_index = match (include_commits, commit_index) {
+            (true, Some(commit_index)) => {
+                timings.push(("update commit index", index_time));
+                let (commit_index, save_error) = commit_index?;
+                warnings.extend(save_error);
+                Some(commit_index)
+            }
+            _ => None,
+        };
+        // the same filters apply to commits, issues and pull requests, e.g. "issues alice opened
+        // last month"
+        let mut filter_config = FilterConfig {
+            authors: Vec::new(),
+            excluded_authors: Vec::new(),
+            date_range: parsed_query.date_range,
+            paths: search_config.paths.clone(),
+            excluded_paths: search_config.excluded_paths.clone(),
+            excluded_keywords: parsed_query.excluded_keywords.clone(),
+            text_filter: parsed_query.text_filter.clone(),
+            git_log_get_all: search_config.search_all,
+        };
+        if !parsed_query.paths.is_empty() || !parsed_query.excluded_paths.is_empty() {
+            // mentions that match no file of the repository are not filters, e.g. "v2.0"
+            let git_client = self.git_client.clone();
+            let all_files = run_blocking(move || git_client.get_all_files()).await??;
+            let resolve_paths = |mentions: &[String], patterns: &mut Vec<String>| {
+                for mention in mentions {
+                    if let Some(pattern) = resolve_path_mention(mention, &all_files) {
+                        if !patterns.contains(&pattern) {
+                            patterns.push(pattern);
+                        }
                     }
                 }
-                filter_config = Some(filter);
+            };
+            resolve_paths(&parsed_query.paths, &mut filter_config.paths);
+            resolve_paths(
+                &parsed_query.excluded_paths,
+                &mut filter_config.excluded_paths,
+            );
+        }
+        if !parsed_query.authors.is_empty() || !parsed_query.excluded_authors.is_empty() {
+            // the logins of the fetched issues let mentions like "@octocat" resolve
+            let mut resolver = self.author_resolver.clone();
+            resolver.add_logins(github_logins(&all_github_issues, &all_github_pull_requests));
+            let resolve_authors = |mentions: &[String], warnings: &mut Vec<GitmError>| {
+                let mut identities: Vec<Identity> = Vec::new();
+                for mention in mentions {
+                    // e.g. a misspelled `author:` qualifier, which would otherwise silently
+                    // match everyone
+                    let Some(identity) = resolver.resolve(mention.as_str()) else {
+                        warnings.push(GitmError::Parse(format!(
+                            "\"{}\" matches no known author and is ignored",
+                            mention
+                        )));
+                        continue;
+                    };
+                    if !identities.contains(identity) {
+                        identities.push(identity.clone());
+                    }
+                }
+                identities
+            };
+            filter_config.authors = resolve_authors(&parsed_query.authors, &mut warnings);
+            filter_config.excluded_authors =
+                resolve_authors(&parsed_query.excluded_authors, &mut warnings);
+        }
+        // the state and labels given on the command line take precedence over the query
+        let mut issue_filter_config = search_config.issue_filter.clone();
+        if issue_filter_config.state.is_none() {
+            issue_filter_config.state = parsed_query.state;
+        }
+        issue_filter_config.merged |= parsed_query.merged;
+        let merge_labels = |labels: &[String], merged: &mut Vec<String>| {
+            for label in labels {
+                if !merged
+                    .iter()
+                    .any(|existing| existing.eq_ignore_ascii_case(label))
+                {
+                    merged.push(label.clone());
+                }
             }
-            let all_git_commits = self.git_client.get_all_commits(filter_config).unwrap();
-            let store = Store::from(all_git_commits.clone());
-            let commit_retriever = BM25Retriever::new();
-            commit_results = commit_retriever
-                .retrieve(
-                    search_config.query.clone(),
-                    store,
-                    search_config.max_num_results,
-                )
-                .unwrap();
-            if search_config.include_code_patches {
-                let code_commits: Vec<Commit> = all_git_commits
+        };
+        merge_labels(&parsed_query.labels, &mut issue_filter_config.labels);
+        merge_labels(
+            &parsed_query.excluded_labels,
+            &mut issue_filter_config.excluded_labels,
+        );
+        all_github_issues
+            .retain(|issue| issue.matches_metadata(&filter_config, &issue_filter_config));
+        all_github_pull_requests.retain(|pull_request| {
+            pull_request.matches_metadata(&filter_config, &issue_filter_config)
+        });
+        let comments_start = Instant::now();
+        match self
+            .add_github_comments(&mut all_github_issues, &mut all_github_pull_requests)
+            .await
+        {
+            Ok(()) => timings.push(("fetch GitHub comments", comments_start.elapsed())),
+            // the issues and pull requests are still searched without their comments
+            Err(e @ GitmError::Cancelled) => return Err(e),
+            Err(e) => warnings.push(e),
+        }
+        all_github_issues.retain(|issue| issue.matches_text(&filter_config));
+        all_github_pull_requests.retain(|pull_request| pull_request.matches_text(&filter_config));
+        // the filter phrases are left out of the text search, e.g. "johns" and "ago"
+        let search_text = parsed_query.search_text.clone();
+        let mut all_git_commits: Vec<Commit> = Vec::new();
+        // statistics of the keyword pass, aligned with the pool below
+        let mut document_stats: Vec<DocumentStats> = Vec::new();
+        if let Some(commit_index) = commit_index {
+            // without classifications and explicit paths the commits are not filtered at all
+            let commit_filter_config = if search_config.disable_classifications
+                && search_config.paths.is_empty()
+                && search_config.excluded_paths.is_empty()
+                && !parsed_query.has_filters()
+            {
+                None
+            } else {
+                Some(filter_config.clone())
+            };
+            let filter_start = Instant::now();
+            // parsing the patches of the commits is the slowest part of loading them
+            (all_git_commits, document_stats) = run_blocking(move || {
+                let commits = commit_index.commits(commit_filter_config.as_ref())?;
+                let document_stats = commits
                     .iter()
-                    .map(|commit| Commit {
-                        display_mode: git::CommitDisplayMode::PatchSetAdd,
-                        ..commit.to_owned()
+                    .map(|commit| match commit_index.stats(&commit.sha) {
+                        Some(stats) => stats.clone(),
+                        None => commit.document().stats(),
                     })
-                    .collect();
-                let store = Store::<Commit>::from(code_commits);
-                let code_ranker = BM25Ranker::builder().splitter(&PuncSplitter).build();
-                let commit_retriever = BM25Retriever::builder().ranker(code_ranker).build();
-                commit_results.append(
-                    &mut commit_retriever
-                        .retrieve(
-                            search_config.query.clone(),
-                            store,
-                            search_config.max_num_results,
-                        )
-                        .unwrap(),
-                );
+                    .collect::<Vec<DocumentStats>>();
+                Ok::<_, GitmError>((commits, document_stats))
+            })
+            .await??;
+            timings.push(("load commits", filter_start.elapsed()));
+        }
+        for issue in all_github_issues.iter() {
+            document_stats.push(issue.document().stats());
+        }
+        for pull_request in all_github_pull_requests.iter() {
+            document_stats.push(pull_request.document().stats());
+        }
+        // commits, issues and pull requests are ranked in one pool so that their scores share the
+        // same corpus statistics and are comparable
+        let pool: Vec<SearchResult> = all_git_commits
+            .into_iter()
+            .map(SearchResult::from)
+            .chain(all_github_issues.into_iter().map(SearchResult::from))
+            .chain(all_github_pull_requests.into_iter().map(SearchResult::from))
+            .collect();
+        let store = Arc::new(Store::from(pool));
+        let num_candidates = search_config.num_fused_results() * FUSION_CANDIDATES_MULTIPLIER;
+        let fusion = &search_config.fusion;
+        // matches in the title, body, code and paths are weighed against each other in one pass,
+        // which runs on its own core next to the semantic pass
+        let keyword_pass = {
+            let store = store.clone();
+            let search_text = search_text.clone();
+            let mut boosts = search_config.field_boosts;
+            if !search_config.include_code_patches {
+                boosts.code = 0.0;
             }
-            // Note: this is a hack to dedupe the results as the size of the results will be small
-            let mut deduped: Vec<Commit> = Vec::new();
-            let mut seen: HashSet<String> = HashSet::new();
-            for commit in commit_results {
-                if !seen.contains(&commit.sha) {
-                    seen.insert(commit.sha.clone());
-                    deduped.push(commit);
+            run_blocking(move || {
+                let index = FieldedIndex::from_document_stats(document_stats.iter());
+                BM25FRanker::builder().boosts(boosts).build().rank_top_k(
+                    search_text,
+                    &index,
+                    &store.data,
+                    num_candidates,
+                )
+            })
+        };
+        let semantic_pass = async {
+            if !search_config.include_semantic {
+                return None;
+            }
+            Some(match &self.model {
+                Some(model) => {
+                    self.semantic_pass(model.clone(), &search_text, &store, num_candidates)
+                        .await
+                }
+                None => {
+                    self.semantic_pass(
+                        HashingEmbeddingBackend::new(),
+                        &search_text,
+                        &store,
+                        num_candidates,
+                    )
+                    .await
                 }
+            })
+        };
+        let ((keyword_results, keyword_time), (semantic_results, semantic_time)) =
+            tokio::join!(timed(keyword_pass), timed(semantic_pass));
+        let mut ranked_lists: Vec<(f64, Vec<RankingResult<SearchResult>>)> = Vec::new();
+        ranked_lists.push((fusion.keyword_weight, keyword_results?));
+        timings.push(("keyword pass", keyword_time));
+        if let Some(semantic_results) = semantic_results {
+            // the other passes are enough to rank the results
+            match semantic_results {
+                Ok(semantic_results) => {
+                    ranked_lists.push((fusion.semantic_weight, semantic_results))
+                }
+                Err(e) => warnings.push(e),
             }
-            commit_results = deduped;
-        }
-        if search_config.include_issues {
-            let all_github_issues = self.github_client.get_all_issues().unwrap();
-            let store = Store::<Issue>::from(all_github_issues);
-            let issue_retriever = BM25Retriever::new();
-            issue_results = issue_retriever
-                .retrieve(
-                    search_config.query.clone(),
-                    store,
-                    search_config.max_num_results,
-                )
-                .unwrap();
+            timings.push(("semantic pass", semantic_time));
+        }
+        let fused = fuse(
+            ranked_lists,
+            fusion.strategy,
+            fusion.rrf_k,
+            search_config.num_fused_results(),
+        );
+        let rerank_start = Instant::now();
+        let mut results = self
+            .maybe_rerank(&search_config, fused, &mut warnings)
+            .await;
+        if search_config.rerank && self.reranker.is_some() {
+            timings.push(("rerank", rerank_start.elapsed()));
+        }
+        for result in results.iter_mut() {
+            result.item.match_comment(search_text.as_str());
+        }
+        timings.push(("total", search_start.elapsed()));
+        Ok(SearchResponse {
+            results,
+            warnings,
+            timings,
+        })
+    }
+
+    // The parsed query and the failures to parse parts of it. The qualifiers of the query syntax
+    // take precedence over the filters in natural language, and with any of them the LLM is not
+    // asked at all. Without its filters the query is still searched for as a whole.
+    async fn parse_query(&self, search_config: &SearchConfig) -> (ParsedQuery, Vec<GitmError>) {
+        let explicit_query = parse_query_syntax(&search_config.query);
+        let mut errors: Vec<GitmError> = explicit_query
+            .errors
+            .into_iter()
+            .map(GitmError::Parse)
+            .collect();
+        let parsed_query = explicit_query.parsed_query;
+        if search_config.disable_classifications || explicit_query.has_qualifiers {
+            return (parsed_query, errors);
+        }
+        match self
+            .query_parser
+            .parse(parsed_query.search_text.clone())
+            .await
+        {
+            Ok(extracted) => (parsed_query.with_extracted(extracted), errors),
+            Err(e) => {
+                errors.push(GitmError::from(e));
+                (parsed_query, errors)
+            }
+        }
+    }
+
+    // Brings the commit index in sync with HEAD. Also returns the failure to save it, after which
+    // it is rebuilt on the next run.
+    async fn update_commit_index(
+        &self,
+        rebuild_index: bool,
+    ) -> Result<(CommitIndex, Option<GitmError>), GitmError> {
+        let git_client = self.git_client.clone();
+        run_blocking(move || {
+            let mut commit_index = CommitIndex::open(&git_client)?;
+            if rebuild_index {
+                commit_index.clear();
+            }
+            let mut save_error = None;
+            if commit_index.update(&git_client)? {
+                save_error = commit_index.save().err();
+            }
+            Ok((commit_index, save_error))
+        })
+        .await?
+    }
+
+    async fn get_github_results(
+        &self,
+        include_issues: bool,
+        include_pull_requests: bool,
+    ) -> Result<(Vec<Issue>, Vec<PullRequest>), GitmError> {
+        tokio::try_join!(
+            async {
+                if !include_issues {
+                    return Ok(Vec::new());
+                }
+                self.github_client.get_all_issues().await
+            },
+            async {
+                if !include_pull_requests {
+                    return Ok(Vec::new());
+                }
+                self.github_client.get_all_pull_requests().await
+            },
+        )
+    }
+
+    // Fetches the comments of the issues and pull requests, which are only needed once the other
+    // filters left some of them.
+    async fn add_github_comments(
+        &self,
+        issues: &mut [Issue],
+        pull_requests: &mut [PullRequest],
+    ) -> Result<(), GitmError> {
+        if issues.is_empty() && pull_requests.is_empty() {
+            return Ok(());
+        }
+        let mut all_comments = self.github_client.get_all_comments().await?;
+        for issue in issues.iter_mut() {
+            issue.comments = all_comments.remove(&issue.number).unwrap_or_default();
         }
-        Ok((commit_results, issue_results))
+        for pull_request in pull_requests.iter_mut() {
+            pull_request.comments = all_comments
+                .remove(&pull_request.number)
+                .unwrap_or_default();
+        }
+        Ok(())
     }
+
+    async fn maybe_rerank<U>(
+        &self,
+        search_config: &SearchConfig,
+        mut fused: Vec<RankingResult<U>>,
+        warnings: &mut Vec<GitmError>,
+    ) -> Vec<RankingResult<U>>
+    where
+        U: Display + Clone + Identifiable,
+    {
+        if let (true, Some(reranker)) = (search_config.rerank, &self.reranker) {
+            fused.truncate(search_config.rerank_top_n);
+            // the fused order is kept if the model fails
+            match reranker
+                .rerank(search_config.query.clone(), fused.clone())
+                .await
+            {
+                Ok(reranked) => fused = reranked,
+                Err(e) => warnings.push(GitmError::from(e)),
+            }
+        }
+        fused.truncate(search_config.max_num_results);
+        fused
+    }
+
+    async fn semantic_pass<B>(
+        &self,
+        backend: B,
+        search_text: &str,
+        store: &Store<SearchResult>,
+        num_candidates: usize,
+    ) -> Result<Vec<RankingResult<SearchResult>>, GitmError>
+    where
+        B: EmbeddingBackend + Sync,
+    {
+        let cache_path = index_dir(&self.git_client)?.join(format!(
+            "embeddings-{}.json",
+            EmbeddingBackend::name(&backend)
+        ));
+        let semantic_retriever =
+            EmbeddingRetriever::new(backend, EmbeddingCache::open(cache_path)?);
+        let semantic_results = semantic_retriever
+            .retrieve(search_text.to_string(), store, num_candidates)
+            .await?;
+        semantic_retriever.save_cache()?;
+        Ok(semantic_results)
+    }
+}
+
+impl QueryParsers {
+    async fn parse(&self, query: String) -> Result<ParsedQuery, ChatError> {
+        match self {
+            QueryParsers::Model(query_parser) => query_parser.parse(query).await,
+            QueryParsers::RuleBased(query_parser) => query_parser.parse(query).await,
+        }
+    }
+}
+
+// The people who committed to the repository. A missing or unreadable .mailmap is ignored.
+fn git_author_resolver(git_client: &git::Client) -> Result<AuthorResolver, GitmError> {
+    let mailmap = git_client
+        .get_work_tree_dir()
+        .and_then(|dir| Mailmap::open(dir.join(".mailmap").as_path()))
+        .unwrap_or_default();
+    Ok(AuthorResolver::new(git_client.get_all_authors()?, &mailmap))
+}
+
+// The output of the future and how long it took.
+async fn timed<F>(future: F) -> (F::Output, Duration)
+where
+    F: Future,
+{
+    let start = Instant::now();
+    let output = future.await;
+    (output, start.elapsed())
+}
+
+fn github_logins(issues: &[Issue], pull_requests: &[PullRequest]) -> Vec<String> {
+    let authors = issues
+        .iter()
+        .flat_map(|issue| {
+            std::iter::once(&issue.author).chain(issue.comments.iter().map(|c| &c.author))
+        })
+        .chain(pull_requests.iter().flat_map(|pull_request| {
+            std::iter::once(&pull_request.author)
+                .chain(pull_request.comments.iter().map(|c| &c.author))
+        }));
+    authors
+        .filter_map(|author| author.username.clone())
+        .collect()
 }
@@ -0,0 +1,184 @@
+use crate::document::{Document, Fielded};
+use crate::fmt::{colorize_string, Color};
+use crate::git::Commit;
+use crate::github::{best_matching_comment, Issue, PullRequest};
+use crate::rankers::RankingResult;
+use crate::store::Identifiable;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResultKind {
+    Commit,
+    Issue,
+    PullRequest,
+}
+
+impl Display for ResultKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResultKind::Commit => write!(f, "commit"),
+            ResultKind::Issue => write!(f, "issue"),
+            ResultKind::PullRequest => write!(f, "pull request"),
+        }
+    }
+}
+
+impl FromStr for ResultKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "commit" | "commits" => Ok(ResultKind::Commit),
+            "issue" | "issues" => Ok(ResultKind::Issue),
+            "pull_request" | "pull request" | "pull requests" | "pr" | "prs" => {
+                Ok(ResultKind::PullRequest)
+            }
+            _ => Err(format!(
+                "unknown result kind: {} (expected \"commit\", \"issue\" or \"pull_request\")",
+                s
+            )),
+        }
+    }
+}
+
+// Anything that can be ranked and shown as a search hit.
+pub trait Searchable: Display + Clone + Identifiable + Fielded {
+    fn kind(&self) -> ResultKind;
+
+    // the human readable form that is printed for a hit
+    fn fmt_result(&self) -> String;
+}
+
+impl Searchable for Commit {
+    fn kind(&self) -> ResultKind {
+        ResultKind::Commit
+    }
+
+    fn fmt_result(&self) -> String {
+        self.mock_git_log_fmt()
+    }
+}
+
+impl Searchable for Issue {
+    fn kind(&self) -> ResultKind {
+        ResultKind::Issue
+    }
+
+    fn fmt_result(&self) -> String {
+        self.mock_gh_issue_fmt()
+    }
+}
+
+impl Searchable for PullRequest {
+    fn kind(&self) -> ResultKind {
+        ResultKind::PullRequest
+    }
+
+    fn fmt_result(&self) -> String {
+        self.mock_gh_pr_fmt()
+    }
+}
+
+// A hit of any kind, so that commits, issues and pull requests can be ranked in one pool.
+#[derive(Debug, Clone)]
+pub enum SearchResult {
+    Commit(Commit),
+    Issue(Issue),
+    PullRequest(PullRequest),
+}
+
+impl Display for SearchResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchResult::Commit(commit) => write!(f, "{}", commit),
+            SearchResult::Issue(issue) => write!(f, "{}", issue),
+            SearchResult::PullRequest(pull_request) => write!(f, "{}", pull_request),
+        }
+    }
+}
+
+impl Identifiable for SearchResult {
+    fn id(&self) -> String {
+        match self {
+            SearchResult::Commit(commit) => commit.id(),
+            SearchResult::Issue(issue) => issue.id(),
+            SearchResult::PullRequest(pull_request) => pull_request.id(),
+        }
+    }
+}
+
+impl Fielded for SearchResult {
+    fn document(&self) -> Document {
+        match self {
+            SearchResult::Commit(commit) => commit.document(),
+            SearchResult::Issue(issue) => issue.document(),
+            SearchResult::PullRequest(pull_request) => pull_request.document(),
+        }
+    }
+}
+
+impl Searchable for SearchResult {
+    fn kind(&self) -> ResultKind {
+        match self {
+            SearchResult::Commit(commit) => commit.kind(),
+            SearchResult::Issue(issue) => issue.kind(),
+            SearchResult::PullRequest(pull_request) => pull_request.kind(),
+        }
+    }
+
+    fn fmt_result(&self) -> String {
+        match self {
+            SearchResult::Commit(commit) => commit.fmt_result(),
+            SearchResult::Issue(issue) => issue.fmt_result(),
+            SearchResult::PullRequest(pull_request) => pull_request.fmt_result(),
+        }
+    }
+}
+
+impl SearchResult {
+    // Points issue and pull request hits to the comment of their thread that matches the query.
+    pub fn match_comment(&mut self, query: &str) {
+        match self {
+            SearchResult::Commit(_) => {}
+            SearchResult::Issue(issue) => {
+                issue.matched_comment = best_matching_comment(&issue.comments, query);
+            }
+            SearchResult::PullRequest(pull_request) => {
+                pull_request.matched_comment = best_matching_comment(&pull_request.comments, query);
+            }
+        }
+    }
+}
+
+impl From<Commit> for SearchResult {
+    fn from(commit: Commit) -> Self {
+        SearchResult::Commit(commit)
+    }
+}
+
+impl From<Issue> for SearchResult {
+    fn from(issue: Issue) -> Self {
+        SearchResult::Issue(issue)
+    }
+}
+
+impl From<PullRequest> for SearchResult {
+    fn from(pull_request: PullRequest) -> Self {
+        SearchResult::PullRequest(pull_request)
+    }
+}
+
+pub fn fmt_ranked_result<T>(result: &RankingResult<T>) -> String
+where
+    T: Searchable,
+{
+    format!(
+        "{}\n{}",
+        colorize_string(
+            format!("[{} | score {:.4}]", result.item.kind(), result.score).as_str(),
+            Color::Cyan
+        ),
+        result.item.fmt_result()
+    )
+}
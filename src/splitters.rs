@@ -1,23 +1,43 @@
 use crate::utils::split_on_punc_and_whitespace;
 
-pub trait Splitter {
+pub trait Splitter: Send + Sync {
     fn split(&self, s: &str) -> Vec<String>;
+
+    // identifies the splitter so that indexes built with it can be cached and reused
+    fn name(&self) -> String;
 }
 
 pub struct WhitespaceSplitter;
 pub struct CharSplitter;
 pub struct PuncSplitter;
+// Splits on everything that is not a letter or a digit, including Unicode punctuation.
+pub struct WordSplitter;
+// Splits source code: identifiers into their camelCase, PascalCase and snake_case words along with
+// the whole identifier, paths into their segments along with the whole path, and numeric literals
+// kept whole. Everything is lowercased so that `parseHttpHeader` matches "parse http header".
+// It is used through `analysis::code_analyzer` for the code and paths fields of a commit: the
+// lines that its patch adds (what `CommitDisplayMode::PatchSetAdd` shows) are split with it when
+// the commit is ingested into the index, and the query when it is ranked against those fields.
+pub struct CodeSplitter;
 
 impl Splitter for WhitespaceSplitter {
     fn split(&self, s: &str) -> Vec<String> {
         s.split_whitespace().map(|s| s.to_string()).collect()
     }
+
+    fn name(&self) -> String {
+        String::from("whitespace")
+    }
 }
 
 impl Splitter for CharSplitter {
     fn split(&self, s: &str) -> Vec<String> {
         s.chars().map(|c| c.to_string()).collect()
     }
+
+    fn name(&self) -> String {
+        String::from("char")
+    }
 }
 
 impl Splitter for PuncSplitter {
@@ -27,4 +47,204 @@ impl Splitter for PuncSplitter {
             .map(|s| s.to_string())
             .collect()
     }
+
+    fn name(&self) -> String {
+        String::from("punc")
+    }
+}
+
+impl Splitter for WordSplitter {
+    fn split(&self, s: &str) -> Vec<String> {
+        s.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_string())
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        String::from("word")
+    }
+}
+
+impl Splitter for CodeSplitter {
+    fn split(&self, s: &str) -> Vec<String> {
+        let mut tokens = Vec::new();
+        // runs of characters that can be part of an identifier, a path or a number
+        for word in s.split(|c: char| !(c.is_alphanumeric() || "_./:-".contains(c))) {
+            let word = word.trim_matches(|c: char| "./:-".contains(c));
+            if word.is_empty() {
+                continue;
+            }
+            if is_numeric_literal(word) {
+                tokens.push(normalize_numeric_literal(word));
+                continue;
+            }
+            let segments: Vec<&str> = word
+                .split(|c: char| "./:-".contains(c))
+                .filter(|segment| !segment.is_empty())
+                .collect();
+            for segment in &segments {
+                if is_numeric_literal(segment) {
+                    tokens.push(normalize_numeric_literal(segment));
+                } else {
+                    split_identifier(segment, &mut tokens);
+                }
+            }
+            if segments.len() > 1 {
+                tokens.push(word.to_lowercase());
+            }
+        }
+        tokens
+    }
+
+    fn name(&self) -> String {
+        String::from("code")
+    }
+}
+
+// Decimal, hex, octal and binary integers and floats, with or without `_` separators and suffixes
+// such as `u64` or `f32`.
+fn is_numeric_literal(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_digit())
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn normalize_numeric_literal(s: &str) -> String {
+    s.replace('_', "").to_lowercase()
+}
+
+// Pushes the words of the identifier and, if there is more than one, the whole identifier. A word
+// starts after an underscore, at a lowercase to uppercase change (`parseHttp`) and at the last
+// capital of an acronym (`HTTPServer`). Digits stay with the word before them (`utf8Decode`).
+fn split_identifier(identifier: &str, tokens: &mut Vec<String>) {
+    let chars: Vec<char> = identifier.chars().collect();
+    let mut words: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '_' {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if c.is_uppercase() && !current.is_empty() {
+            let previous = chars[i - 1];
+            let is_next_lowercase = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && is_next_lowercase)
+            {
+                words.push(std::mem::take(&mut current));
+            }
+        }
+        current.extend(c.to_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    let is_compound = words.len() > 1;
+    tokens.extend(words);
+    if is_compound {
+        tokens.push(identifier.trim_matches('_').to_lowercase());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(s: &str) -> Vec<String> {
+        CodeSplitter.split(s)
+    }
+
+    #[test]
+    fn test_camel_case() {
+        assert_eq!(
+            split("parseHttpHeader"),
+            vec!["parse", "http", "header", "parsehttpheader"]
+        );
+        assert_eq!(split("TokenStream"), vec!["token", "stream", "tokenstream"]);
+        assert_eq!(split("parse"), vec!["parse"]);
+    }
+
+    #[test]
+    fn test_acronyms() {
+        assert_eq!(split("HTTPServer"), vec!["http", "server", "httpserver"]);
+        assert_eq!(
+            split("getHTTPResponse"),
+            vec!["get", "http", "response", "gethttpresponse"]
+        );
+        assert_eq!(split("parseURL"), vec!["parse", "url", "parseurl"]);
+        assert_eq!(split("HTTP"), vec!["http"]);
+    }
+
+    #[test]
+    fn test_digits() {
+        assert_eq!(split("utf8Decode"), vec!["utf8", "decode", "utf8decode"]);
+        assert_eq!(split("sha256Sum"), vec!["sha256", "sum", "sha256sum"]);
+        assert_eq!(split("i32"), vec!["i32"]);
+    }
+
+    #[test]
+    fn test_snake_case() {
+        assert_eq!(
+            split("parse_http_header"),
+            vec!["parse", "http", "header", "parse_http_header"]
+        );
+        assert_eq!(
+            split("MAX_RETRY_COUNT"),
+            vec!["max", "retry", "count", "max_retry_count"]
+        );
+        assert_eq!(split("__init__"), vec!["init"]);
+    }
+
+    #[test]
+    fn test_path_segments() {
+        assert_eq!(
+            split("src/parser/lexer.rs"),
+            vec!["src", "parser", "lexer", "rs", "src/parser/lexer.rs"]
+        );
+        assert_eq!(
+            split("crate::io::read_line"),
+            vec![
+                "crate",
+                "io",
+                "read",
+                "line",
+                "read_line",
+                "crate::io::read_line"
+            ]
+        );
+        // punctuation around a path is not part of it
+        assert_eq!(
+            split("(./src/main.rs)."),
+            vec!["src", "main", "rs", "src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn test_numeric_literals() {
+        assert_eq!(split("0xFF_FF"), vec!["0xffff"]);
+        assert_eq!(split("1_000_000u64"), vec!["1000000u64"]);
+        assert_eq!(split("3.14"), vec!["3.14"]);
+        assert_eq!(split("v1.2.3"), vec!["v1", "2", "3", "v1.2.3"]);
+    }
+
+    #[test]
+    fn test_code() {
+        assert_eq!(
+            split("let len = buf.readLine(0);"),
+            vec![
+                "let",
+                "len",
+                "buf",
+                "read",
+                "line",
+                "readline",
+                "buf.readline",
+                "0"
+            ]
+        );
+    }
 }
@@ -1,6 +1,11 @@
 use std::fmt::Display;
 use std::sync::Mutex;
 
+// Stable identifier of a stored item (e.g. a commit SHA or an issue number), used to key caches.
+pub trait Identifiable {
+    fn id(&self) -> String;
+}
+
 pub struct Store<T>
 where
     T: Display + Clone,
@@ -14,14 +19,18 @@ where
     T: Display + Clone,
 {
     pub fn get(&self, index: usize) -> Option<&T> {
-        let _ = self.mu.lock();
+        let _guard = self.mu.lock();
         self.data.get(index)
     }
 
     pub fn len(&self) -> usize {
-        let _ = self.mu.lock();
+        let _guard = self.mu.lock();
         self.data.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 impl<T> From<Vec<T>> for Store<T>
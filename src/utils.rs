@@ -1,8 +1,9 @@
+use crate::error::GitmError;
 use regex::Regex;
 use std::path::Path;
 use std::process::Command;
 
-pub fn does_valid_git_dir_exist() -> Result<bool, Box<dyn std::error::Error>> {
+pub fn does_valid_git_dir_exist() -> Result<bool, GitmError> {
     let cwd = std::env::current_dir()?;
     let path = Path::new(cwd.as_path());
     if !path.exists() {
@@ -27,10 +28,25 @@ pub fn does_valid_git_dir_exist() -> Result<bool, Box<dyn std::error::Error>> {
     }
 }
 
-pub fn does_command_exist(command: &str) -> Result<bool, Box<dyn std::error::Error>> {
+pub fn does_command_exist(command: &str) -> Result<bool, GitmError> {
     match Command::new("which").arg(command).output() {
         Ok(output) => Ok(output.status.success() && !String::from_utf8(output.stdout)?.is_empty()),
-        Err(err) => Err(Box::new(err)),
+        Err(err) => Err(GitmError::Io(err)),
+    }
+}
+
+// Runs blocking work (git, gh or CPU-bound ranking) on tokio's blocking threads so that it does
+// not stall the other stages of a search. A panic of the work is resumed in the caller and a task
+// that was cancelled, e.g. because the runtime shuts down, is an error.
+pub async fn run_blocking<T, F>(f: F) -> Result<T, GitmError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => Ok(result),
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(_) => Err(GitmError::Cancelled),
     }
 }
 
@@ -38,3 +54,25 @@ pub fn split_on_punc_and_whitespace(s: &str) -> Vec<&str> {
     let re = Regex::new(r"[[:punct:]\s\n]+").unwrap();
     re.split(s).collect()
 }
+
+// Lowercases the query and splits it into words, keeping the punctuation inside of words (dates,
+// versions, emails) but not around them.
+pub fn split_query_words(query: &str) -> Vec<String> {
+    query
+        .split_whitespace()
+        .map(|word| {
+            word.trim_matches(|c: char| ",.;!?\"()[]".contains(c))
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+// The singular of an English plural that ends in "s", e.g. "bumps", which is good enough for
+// matching keywords as substrings.
+pub fn strip_plural(word: &str) -> &str {
+    match word.strip_suffix('s') {
+        Some(singular) if singular.len() > 2 => singular,
+        _ => word,
+    }
+}